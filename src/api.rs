@@ -0,0 +1,1093 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+lazy_static! {
+    static ref HTML_TAG_PATTERN: regex::Regex = regex::Regex::new(r"<[^>]+>").unwrap();
+    static ref REFRESH_CSRF_PATTERN: regex::Regex =
+        regex::Regex::new(r#"<div id="1-name">([^<]+)</div>"#).unwrap();
+}
+
+/// 哔哩哔哩的WEB接口, 使用共享的HTTP客户端
+pub(crate) struct WebApi {
+    agent: reqwest::Client,
+    sess_data: Option<String>,
+    buvid: String,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    code: i64,
+    #[serde(default)]
+    message: String,
+    /// 番剧相关的接口使用result
+    #[serde(alias = "result")]
+    data: Option<T>,
+}
+
+/// 接口返回的错误
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "接口返回错误 : {} : {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// 是否因为地区限制不能观看
+    /// -10403 : 抱歉您所在地区不可观看
+    pub(crate) fn is_region_locked(&self) -> bool {
+        self.code == -10403 || self.message.contains("地区")
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct Nav {
+    #[serde(rename = "isLogin")]
+    pub is_login: bool,
+    pub mid: i64,
+    pub uname: String,
+    pub level_info: LevelInfo,
+    /// 硬币
+    pub money: f64,
+    /// 0:无 1:月度大会员 2:年度以上大会员
+    #[serde(rename = "vipType")]
+    pub vip_type: i64,
+    /// 1:有效
+    #[serde(rename = "vipStatus")]
+    pub vip_status: i64,
+    /// 大会员过期时间(毫秒)
+    #[serde(rename = "vipDueDate")]
+    pub vip_due_date: i64,
+    pub vip_label: VipLabel,
+}
+
+impl Nav {
+    pub(crate) fn is_vip(&self) -> bool {
+        self.vip_status == 1 && self.vip_type > 0
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct LevelInfo {
+    pub current_level: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct VipLabel {
+    pub text: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct CookieInfo {
+    pub refresh: bool,
+    pub timestamp: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct LoginQr {
+    /// 二维码的内容
+    pub url: String,
+    pub qrcode_key: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct LoginQrPoll {
+    /// 0 : 成功, 86038 : 二维码已过期, 86090 : 已扫描未确认, 86101 : 未扫描
+    pub code: i64,
+    pub message: String,
+    /// 成功时为包含cookie的url
+    pub url: String,
+    pub refresh_token: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct CookieRefresh {
+    pub refresh_token: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SearchPage<T> {
+    pub page: i64,
+    #[serde(rename = "numPages")]
+    pub num_pages: i64,
+    #[serde(rename = "numResults")]
+    pub num_results: i64,
+    pub result: Vec<T>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SearchVideo {
+    pub bvid: String,
+    pub title: String,
+    pub author: String,
+    pub duration: String,
+    pub play: i64,
+    pub pubdate: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SearchBangumi {
+    pub season_id: i64,
+    pub title: String,
+    pub season_type_name: String,
+    pub ep_size: i64,
+    pub pubtime: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SearchUser {
+    pub mid: i64,
+    pub uname: String,
+    pub fans: i64,
+    pub videos: i64,
+    pub level: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SpaceVideoPage {
+    pub list: SpaceVideoList,
+    pub page: SpacePage,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SpaceVideoList {
+    pub vlist: Vec<SpaceVideo>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SpaceVideo {
+    pub bvid: String,
+    pub title: String,
+    pub created: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcSeason {
+    pub season_id: i64,
+    pub title: String,
+    pub season_title: String,
+    /// 海报
+    pub cover: String,
+    pub episodes: Vec<PgcEpisode>,
+    /// 正片以外的内容, 例如 PV / SP / OVA
+    pub section: Vec<PgcSection>,
+    /// 可以播放的地区
+    pub areas: Vec<PgcArea>,
+    /// 所属的系列
+    pub series: PgcSeries,
+    /// 系列中的全部季, 只有一季时可能为空
+    pub seasons: Vec<PgcSeasonItem>,
+}
+
+impl PgcSeason {
+    /// 系列的名称, 没有系列时使用番剧的标题
+    pub(crate) fn series_title(&self) -> &str {
+        if self.series.series_title.is_empty() {
+            self.title.as_str()
+        } else {
+            self.series.series_title.as_str()
+        }
+    }
+
+    /// 系列中的全部季, 没有返回时为这一季
+    pub(crate) fn season_list(&self) -> Vec<PgcSeasonItem> {
+        if self.seasons.is_empty() {
+            vec![PgcSeasonItem {
+                season_id: self.season_id,
+                season_title: self.season_title.clone(),
+            }]
+        } else {
+            self.seasons.clone()
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcSeries {
+    pub series_title: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcSeasonItem {
+    pub season_id: i64,
+    /// 例如 第一季 / 第二季
+    pub season_title: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcArea {
+    pub id: i64,
+    /// 例如 中国大陆 / 中国港澳台
+    pub name: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcSection {
+    pub title: String,
+    pub episodes: Vec<PgcEpisode>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcEpisode {
+    /// ep_id
+    pub id: i64,
+    pub aid: i64,
+    pub bvid: String,
+    pub cid: i64,
+    /// 集数, 例如 1 / 12.5 / PV
+    pub title: String,
+    pub long_title: String,
+    /// 例如 第1话 标题
+    pub show_title: String,
+    /// 例如 会员 / 付费 / 限免 / 预告
+    pub badge: String,
+    /// 2:免费 13:大会员 其他为付费
+    pub status: i64,
+}
+
+impl PgcEpisode {
+    /// 集数的名称, 例如 第1话, show_title去掉标题的部分
+    pub(crate) fn title_format(&self) -> String {
+        match self
+            .show_title
+            .trim()
+            .strip_suffix(self.long_title.trim())
+            .map(str::trim)
+        {
+            Some(title_format) if !title_format.is_empty() => title_format.to_owned(),
+            _ => self.title.clone(),
+        }
+    }
+
+    /// 预告和PV
+    pub(crate) fn is_preview(&self) -> bool {
+        self.badge.contains("预告") || self.title.to_uppercase().contains("PV")
+    }
+
+    /// 需要大会员
+    pub(crate) fn requires_vip(&self) -> bool {
+        self.badge == "会员" || self.status == 13
+    }
+
+    /// 需要单独购买
+    pub(crate) fn is_paid(&self) -> bool {
+        self.badge.contains("付费") || (self.status != 0 && self.status != 2 && self.status != 13)
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcPlayUrl {
+    pub dash: PgcDash,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcDash {
+    pub video: Vec<PgcDashMedia>,
+    pub audio: Vec<PgcDashMedia>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PgcDashMedia {
+    pub id: i64,
+    #[serde(alias = "baseUrl")]
+    pub base_url: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PlayUrl {
+    /// 可以选择的清晰度
+    pub support_formats: Vec<SupportFormat>,
+    /// fnval为DASH时的音视频
+    pub dash: PgcDash,
+    /// fnval为MP4时的视频
+    pub durl: Vec<Durl>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SupportFormat {
+    pub quality: i64,
+    /// 例如 1080P 高清
+    pub new_description: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct Durl {
+    pub url: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct UserCard {
+    pub card: UserCardInfo,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct UserCardInfo {
+    pub name: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct ArchiveView {
+    pub aid: i64,
+    pub bvid: String,
+    /// 第一个分P
+    pub cid: i64,
+    pub title: String,
+    /// 封面
+    pub pic: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct FavFolderInfo {
+    pub title: String,
+    pub cover: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct FavResourcePage {
+    pub info: FavFolderInfo,
+    pub medias: Vec<FavMedia>,
+    pub has_more: bool,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct FavMedia {
+    pub bvid: String,
+    pub title: String,
+    pub intro: String,
+    /// 分P的数量
+    pub page: i64,
+    /// 2:视频 12:音频 21:合集
+    #[serde(rename = "type")]
+    pub media_type: i64,
+    /// 最低位为1时已失效
+    pub attr: i64,
+}
+
+impl FavMedia {
+    /// 已经失效(被删除)的视频或者不是视频的收藏
+    pub(crate) fn is_invalid(&self) -> bool {
+        self.media_type != 2 || self.attr & 1 == 1 || self.title == "已失效视频"
+    }
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct CollectionArchives {
+    pub meta: CollectionMeta,
+    pub archives: Vec<CollectionArchive>,
+    pub page: CollectionPage,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct CollectionArchive {
+    pub bvid: String,
+    pub title: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct CollectionPage {
+    pub page_num: i64,
+    pub page_size: i64,
+    pub total: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct CollectionMeta {
+    pub name: String,
+    pub cover: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PlayerInfo {
+    /// UP主设置的章节, 没有时为空
+    pub view_points: Vec<ViewPoint>,
+    pub subtitle: PlayerSubtitle,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct PlayerSubtitle {
+    /// 视频的字幕, 没有字幕或者没有登录时为空
+    pub subtitles: Vec<SubtitleInfo>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct SubtitleInfo {
+    /// 语言, 例如 zh-CN / ai-zh
+    pub lan: String,
+    /// 语言的名称, 例如 中文（中国）
+    pub lan_doc: String,
+    /// 字幕文件的地址, 没有协议
+    pub subtitle_url: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SubtitleBody {
+    pub body: Vec<SubtitleLine>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SubtitleLine {
+    /// 开始和结束的时间(秒)
+    pub from: f64,
+    pub to: f64,
+    pub content: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ViewPoint {
+    /// 章节的标题
+    pub content: String,
+    /// 开始和结束的时间(秒)
+    pub from: i64,
+    pub to: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct FavFolderList {
+    pub list: Vec<FavFolder>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct FavFolder {
+    pub id: i64,
+    pub title: String,
+    pub media_count: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct ToView {
+    pub list: Vec<ToViewVideo>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct ToViewVideo {
+    pub bvid: String,
+    pub title: String,
+    /// 时长(秒)
+    pub duration: i64,
+    pub owner: VideoOwner,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct VideoOwner {
+    pub mid: i64,
+    pub name: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct FollowingPage {
+    pub list: Vec<Following>,
+    pub total: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct Following {
+    pub mid: i64,
+    pub uname: String,
+    pub sign: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct BangumiFollowPage {
+    pub list: Vec<BangumiFollow>,
+    pub total: i64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct BangumiFollow {
+    pub season_id: i64,
+    pub title: String,
+    pub season_type_name: String,
+    pub new_ep: BangumiNewEp,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct BangumiNewEp {
+    /// 例如 更新至第12话
+    pub index_show: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SpacePage {
+    pub count: i64,
+    pub pn: i64,
+    pub ps: i64,
+}
+
+impl WebApi {
+    pub(crate) fn new() -> Self {
+        Self {
+            agent: crate::http::http_client(),
+            sess_data: None,
+            buvid: uuid::Uuid::new_v4().to_string() + "infoc",
+        }
+    }
+
+    pub(crate) fn set_sess_data(&mut self, sess_data: String) {
+        self.sess_data = Some(sess_data);
+    }
+
+    fn cookie(&self) -> String {
+        match &self.sess_data {
+            Some(sess_data) => format!("buvid3={}; SESSDATA={}", self.buvid, sess_data),
+            None => format!("buvid3={}", self.buvid),
+        }
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        self.agent
+            .get(url)
+            .timeout(crate::http::request_timeout())
+            .header("referer", "https://www.bilibili.com")
+            .header("cookie", self.cookie())
+    }
+
+    fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        self.agent
+            .post(url)
+            .timeout(crate::http::request_timeout())
+            .header("referer", "https://www.bilibili.com")
+            .header("cookie", self.cookie())
+    }
+
+    async fn get_data<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, String)],
+    ) -> crate::Result<T> {
+        let rsp: Response<T> = self
+            .get(url)
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        response_data(rsp)
+    }
+
+    /// 登录状态和用户信息, 未登录时is_login为false
+    pub(crate) async fn nav(&self) -> crate::Result<Nav> {
+        let rsp: Response<Nav> = self
+            .get("https://api.bilibili.com/x/web-interface/nav")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // -101 : 账号未登录
+        if rsp.code == -101 {
+            return Ok(Nav::default());
+        }
+        response_data(rsp)
+    }
+
+    /// 申请登录二维码
+    pub(crate) async fn login_qr(&self) -> crate::Result<LoginQr> {
+        self.get_data(
+            "https://passport.bilibili.com/x/passport-login/web/qrcode/generate",
+            &[],
+        )
+        .await
+    }
+
+    /// 扫码登录的状态, 成功时同时返回refresh_token
+    pub(crate) async fn login_qr_poll(&self, qrcode_key: &str) -> crate::Result<LoginQrPoll> {
+        self.get_data(
+            "https://passport.bilibili.com/x/passport-login/web/qrcode/poll",
+            &[("qrcode_key", qrcode_key.to_owned())],
+        )
+        .await
+    }
+
+    /// 检查cookie是否需要刷新
+    pub(crate) async fn cookie_info(&self, csrf: &str) -> crate::Result<CookieInfo> {
+        self.get_data(
+            "https://passport.bilibili.com/x/passport-login/web/cookie/info",
+            &[("csrf", csrf.to_owned())],
+        )
+        .await
+    }
+
+    /// 获取刷新cookie时需要的refresh_csrf
+    pub(crate) async fn refresh_csrf(&self, correspond_path: &str) -> crate::Result<String> {
+        let html = self
+            .get(format!("https://www.bilibili.com/correspond/1/{}", correspond_path).as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(REFRESH_CSRF_PATTERN
+            .captures(html.as_str())
+            .with_context(|| "未能取得refresh_csrf")?
+            .get(1)
+            .unwrap()
+            .as_str()
+            .to_owned())
+    }
+
+    /// 刷新cookie, 返回新的refresh_token和服务器设置的cookie
+    pub(crate) async fn cookie_refresh(
+        &self,
+        csrf: &str,
+        refresh_csrf: &str,
+        refresh_token: &str,
+    ) -> crate::Result<(CookieRefresh, HashMap<String, String>)> {
+        let rsp = self
+            .post("https://passport.bilibili.com/x/passport-login/web/cookie/refresh")
+            .form(&[
+                ("csrf", csrf),
+                ("refresh_csrf", refresh_csrf),
+                ("source", "main_web"),
+                ("refresh_token", refresh_token),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+        let mut cookies = HashMap::new();
+        for set_cookie in rsp.headers().get_all("set-cookie") {
+            if let Some((k, v)) = set_cookie
+                .to_str()?
+                .split(';')
+                .next()
+                .and_then(|kv| kv.split_once('='))
+            {
+                cookies.insert(k.trim().to_owned(), v.trim().to_owned());
+            }
+        }
+        let rsp: Response<CookieRefresh> = rsp.json().await?;
+        Ok((response_data(rsp)?, cookies))
+    }
+
+    /// 确认刷新, 使旧的refresh_token失效
+    pub(crate) async fn confirm_refresh(
+        &self,
+        csrf: &str,
+        refresh_token: &str,
+    ) -> crate::Result<()> {
+        let rsp: Response<serde_json::Value> = self
+            .post("https://passport.bilibili.com/x/passport-login/web/confirm/refresh")
+            .form(&[("csrf", csrf), ("refresh_token", refresh_token)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if rsp.code != 0 {
+            return Err(response_error(&rsp));
+        }
+        Ok(())
+    }
+
+    async fn search<T: DeserializeOwned + Default>(
+        &self,
+        search_type: &str,
+        keyword: &str,
+        order: &str,
+        duration: i64,
+        page: i64,
+    ) -> crate::Result<SearchPage<T>> {
+        self.get_data(
+            "https://api.bilibili.com/x/web-interface/search/type",
+            &[
+                ("search_type", search_type.to_owned()),
+                ("keyword", keyword.to_owned()),
+                ("order", order.to_owned()),
+                ("duration", duration.to_string()),
+                ("page", page.to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// 搜索视频
+    pub(crate) async fn search_video(
+        &self,
+        keyword: &str,
+        order: &str,
+        duration: i64,
+        page: i64,
+    ) -> crate::Result<SearchPage<SearchVideo>> {
+        self.search("video", keyword, order, duration, page).await
+    }
+
+    /// 搜索番剧
+    pub(crate) async fn search_bangumi(
+        &self,
+        keyword: &str,
+        order: &str,
+        page: i64,
+    ) -> crate::Result<SearchPage<SearchBangumi>> {
+        self.search("media_bangumi", keyword, order, 0, page).await
+    }
+
+    /// 搜索用户
+    pub(crate) async fn search_user(
+        &self,
+        keyword: &str,
+        order: &str,
+        page: i64,
+    ) -> crate::Result<SearchPage<SearchUser>> {
+        self.search("bili_user", keyword, order, 0, page).await
+    }
+
+    /// 用户信息
+    pub(crate) async fn user_card(&self, mid: i64) -> crate::Result<UserCard> {
+        self.get_data(
+            "https://api.bilibili.com/x/web-interface/card",
+            &[("mid", mid.to_string())],
+        )
+        .await
+    }
+
+    /// UP主投稿的视频
+    pub(crate) async fn space_video_page(
+        &self,
+        mid: i64,
+        pn: i64,
+        ps: i64,
+    ) -> crate::Result<SpaceVideoPage> {
+        self.get_data(
+            "https://api.bilibili.com/x/space/arc/search",
+            &[
+                ("mid", mid.to_string()),
+                ("pn", pn.to_string()),
+                ("ps", ps.to_string()),
+                ("order", "pubdate".to_owned()),
+            ],
+        )
+        .await
+    }
+
+    /// 视频的信息
+    pub(crate) async fn archive_view(&self, bvid: &str) -> crate::Result<ArchiveView> {
+        self.get_data(
+            "https://api.bilibili.com/x/web-interface/view",
+            &[("bvid", bvid.to_owned())],
+        )
+        .await
+    }
+
+    /// 收藏夹的信息
+    pub(crate) async fn fav_folder_info(&self, fid: i64) -> crate::Result<FavFolderInfo> {
+        self.get_data(
+            "https://api.bilibili.com/x/v3/fav/folder/info",
+            &[("media_id", fid.to_string())],
+        )
+        .await
+    }
+
+    /// 合集的信息和一页视频, 从旧到新排列
+    pub(crate) async fn collection_archives(
+        &self,
+        mid: i64,
+        sid: i64,
+        page_num: i64,
+        page_size: i64,
+    ) -> crate::Result<CollectionArchives> {
+        self.get_data(
+            "https://api.bilibili.com/x/polymer/web-space/seasons_archives_list",
+            &[
+                ("mid", mid.to_string()),
+                ("season_id", sid.to_string()),
+                ("sort_reverse", "false".to_owned()),
+                ("page_num", page_num.to_string()),
+                ("page_size", page_size.to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// 收藏夹中的一页视频, 按照收藏时间从新到旧排列
+    pub(crate) async fn fav_resource_page(
+        &self,
+        fid: i64,
+        pn: i64,
+        ps: i64,
+    ) -> crate::Result<FavResourcePage> {
+        self.get_data(
+            "https://api.bilibili.com/x/v3/fav/resource/list",
+            &[
+                ("media_id", fid.to_string()),
+                ("pn", pn.to_string()),
+                ("ps", ps.to_string()),
+                ("order", "mtime".to_owned()),
+                ("platform", "web".to_owned()),
+            ],
+        )
+        .await
+    }
+
+    /// 视频的播放地址, fnval为 FNVAL_DASH 或 FNVAL_MP4
+    pub(crate) async fn play_url(
+        &self,
+        bvid: &str,
+        cid: i64,
+        fnval: i64,
+        qn: i64,
+    ) -> crate::Result<PlayUrl> {
+        self.get_data(
+            "https://api.bilibili.com/x/player/playurl",
+            &[
+                ("bvid", bvid.to_owned()),
+                ("cid", cid.to_string()),
+                ("qn", qn.to_string()),
+                ("fnval", fnval.to_string()),
+                ("fourk", "1".to_owned()),
+            ],
+        )
+        .await
+    }
+
+    /// 播放器的信息, 包含视频的章节
+    pub(crate) async fn player_info(&self, bvid: &str, cid: i64) -> crate::Result<PlayerInfo> {
+        self.get_data(
+            "https://api.bilibili.com/x/player/v2",
+            &[("bvid", bvid.to_owned()), ("cid", cid.to_string())],
+        )
+        .await
+    }
+
+    /// 字幕文件的内容
+    pub(crate) async fn subtitle_body(&self, subtitle_url: &str) -> crate::Result<SubtitleBody> {
+        let url = match subtitle_url.strip_prefix("//") {
+            Some(url) => format!("https://{}", url),
+            None => subtitle_url.replacen("http://", "https://", 1),
+        };
+        Ok(self
+            .get(url.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// 弹幕的xml, 服务器返回的是deflate压缩的数据
+    pub(crate) async fn danmaku_xml(&self, cid: i64) -> crate::Result<String> {
+        let data = self
+            .get(format!("https://comment.bilibili.com/{}.xml", cid).as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        if data.starts_with(b"<?xml") {
+            return Ok(String::from_utf8_lossy(&data).into_owned());
+        }
+        let data = miniz_oxide::inflate::decompress_to_vec(&data)
+            .map_err(|err| anyhow::Error::msg(format!("未能解压弹幕 : {:?}", err)))?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    /// 用户创建的收藏夹, 没有收藏夹时为空
+    pub(crate) async fn fav_folders(&self, mid: i64) -> crate::Result<Vec<FavFolder>> {
+        let rsp: Response<FavFolderList> = self
+            .get("https://api.bilibili.com/x/v3/fav/folder/created/list-all")
+            .query(&[("up_mid", mid.to_string())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if rsp.code != 0 {
+            return Err(response_error(&rsp));
+        }
+        Ok(rsp.data.map(|data| data.list).unwrap_or_default())
+    }
+
+    /// 稍后再看, 需要登录
+    pub(crate) async fn to_view(&self) -> crate::Result<ToView> {
+        self.get_data("https://api.bilibili.com/x/v2/history/toview", &[])
+            .await
+    }
+
+    /// 关注的UP主
+    pub(crate) async fn followings(
+        &self,
+        mid: i64,
+        pn: i64,
+        ps: i64,
+    ) -> crate::Result<FollowingPage> {
+        self.get_data(
+            "https://api.bilibili.com/x/relation/followings",
+            &[
+                ("vmid", mid.to_string()),
+                ("pn", pn.to_string()),
+                ("ps", ps.to_string()),
+                ("order", "desc".to_owned()),
+            ],
+        )
+        .await
+    }
+
+    /// 追的番剧
+    pub(crate) async fn bangumi_follows(
+        &self,
+        mid: i64,
+        pn: i64,
+        ps: i64,
+    ) -> crate::Result<BangumiFollowPage> {
+        self.get_data(
+            "https://api.bilibili.com/x/space/bangumi/follow/list",
+            &[
+                ("type", "1".to_owned()),
+                ("vmid", mid.to_string()),
+                ("pn", pn.to_string()),
+                ("ps", ps.to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// 请求其他服务器上的接口, 只有哔哩哔哩的服务器会带上cookie
+    fn get_endpoint(&self, endpoint: &str, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}{}", endpoint.trim_end_matches('/'), path);
+        let is_bilibili = reqwest::Url::parse(url.as_str())
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.ends_with("bilibili.com")))
+            .unwrap_or(false);
+        if is_bilibili {
+            self.get(url.as_str())
+        } else {
+            self.agent
+                .get(url.as_str())
+                .timeout(crate::http::request_timeout())
+                .header("referer", "https://www.bilibili.com")
+        }
+    }
+
+    /// 番剧的信息, id为 ss开头的season_id 或 ep开头的ep_id
+    pub(crate) async fn pgc_season(&self, endpoint: &str, id: &str) -> crate::Result<PgcSeason> {
+        let query = match id.strip_prefix("ep") {
+            Some(ep_id) => ("ep_id", ep_id.to_owned()),
+            None => ("season_id", id.trim_start_matches("ss").to_owned()),
+        };
+        let rsp: Response<PgcSeason> = self
+            .get_endpoint(endpoint, "/pgc/view/web/season")
+            .query(&[query])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        response_data(rsp)
+    }
+
+    /// 番剧的播放地址, area为服务器所在的地区, 用于代理服务器选择线路
+    pub(crate) async fn pgc_play_url(
+        &self,
+        endpoint: &str,
+        aid: i64,
+        bvid: &str,
+        cid: i64,
+        area: &str,
+    ) -> crate::Result<PgcPlayUrl> {
+        let rsp: Response<PgcPlayUrl> = self
+            .get_endpoint(endpoint, "/pgc/player/web/playurl")
+            .query(&[
+                ("avid", aid.to_string()),
+                ("bvid", bvid.to_owned()),
+                ("cid", cid.to_string()),
+                ("qn", "127".to_owned()),
+                ("fnval", "4048".to_owned()),
+                ("fourk", "1".to_owned()),
+                ("area", area.to_owned()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        response_data(rsp)
+    }
+}
+
+fn response_error<T>(rsp: &Response<T>) -> anyhow::Error {
+    anyhow::Error::new(ApiError {
+        code: rsp.code,
+        message: rsp.message.clone(),
+    })
+}
+
+fn response_data<T>(rsp: Response<T>) -> crate::Result<T> {
+    if rsp.code != 0 {
+        return Err(response_error(&rsp));
+    }
+    rsp.data.with_context(|| "接口未返回数据")
+}
+
+/// 去掉搜索结果中高亮关键字的html标签
+pub(crate) fn strip_html(text: &str) -> String {
+    HTML_TAG_PATTERN
+        .replace_all(text, "")
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+}
@@ -7,12 +7,55 @@ pub(crate) static MATCHES: OnceCell<ArgMatches> = OnceCell::new();
 
 pub fn app() -> Command {
     Command::new("bili-cli")
+        .arg(profile())
+        .arg(config_override())
+        .arg(muxer())
         .subcommand(
             Command::new("login")
-                .about("使用二维码登录")
-                .arg(qr_console()),
+                .about("使用二维码或浏览器的cookie登录")
+                .arg(qr_console())
+                .arg(login_timeout())
+                .arg(cookies())
+                .arg(sess_data())
+                .arg(refresh_token()),
         )
         .subcommand(Command::new("user").about("用户信息"))
+        .subcommand(
+            Command::new("profile")
+                .about("账号管理")
+                .subcommand(Command::new("list").about("列出已经登录的账号"))
+                .subcommand(Command::new("remove").about("删除账号").arg(profile_name()))
+                .subcommand(
+                    Command::new("default")
+                        .about("设置默认账号")
+                        .arg(profile_name()),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("配置")
+                .subcommand_required(true)
+                .subcommand(Command::new("get").about("显示配置的值").arg(config_key()))
+                .subcommand(
+                    Command::new("set")
+                        .about("修改配置, 保存在数据库中, 优先于配置文件")
+                        .arg(config_key())
+                        .arg(config_value()),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("删除使用config set修改的配置")
+                        .arg(config_key()),
+                )
+                .subcommand(Command::new("list").about("显示全部配置以及来源"))
+                .subcommand(Command::new("edit").about("使用编辑器修改配置文件"))
+                .subcommand(
+                    Command::new("lock")
+                        .about("加密保存的登录信息")
+                        .arg(key_file()),
+                )
+                .subcommand(Command::new("unlock").about("解密保存的登录信息")),
+        )
         .subcommand(
             Command::new("down")
                 .about("下载视频")
@@ -20,7 +63,103 @@ pub fn app() -> Command {
                 .arg(url())
                 .arg(parse_input_url())
                 .arg(choose_seasons())
-                .arg(resume_download()),
+                .arg(choose_episodes())
+                .arg(episodes())
+                .arg(items())
+                .arg(latest())
+                .arg(extras())
+                .arg(cover())
+                .arg(thumbnails())
+                .arg(transcode())
+                .arg(resume_download())
+                .arg(output_dir()),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("搜索并选择下载")
+                .arg(keyword())
+                .arg(search_type())
+                .arg(search_order())
+                .arg(search_duration())
+                .arg(search_page())
+                .arg(format())
+                .arg(choose_seasons())
+                .arg(choose_episodes())
+                .arg(episodes())
+                .arg(items())
+                .arg(latest())
+                .arg(extras())
+                .arg(cover())
+                .arg(thumbnails())
+                .arg(transcode())
+                .arg(resume_download())
+                .arg(output_dir()),
+        )
+        .subcommand(
+            Command::new("bangumi")
+                .about("追番, 只下载新的剧集")
+                .arg(transcode())
+                .arg(resume_download())
+                .arg(output_dir())
+                .subcommand(Command::new("follow").about("追番").arg(season()))
+                .subcommand(Command::new("unfollow").about("取消追番").arg(season()))
+                .subcommand(Command::new("list").about("列出追番"))
+                .subcommand(Command::new("update").about("下载追番中新的剧集")),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("定时检查订阅的UP主/收藏夹/合集/番剧, 下载新的视频")
+                .arg(watch_once())
+                .arg(log_file())
+                .arg(transcode())
+                .arg(resume_download())
+                .arg(output_dir())
+                .subcommand(
+                    Command::new("add")
+                        .about("添加订阅")
+                        .arg(subscription_url())
+                        .arg(watch_interval()),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("取消订阅")
+                        .arg(subscription_url()),
+                )
+                .subcommand(Command::new("list").about("列出订阅")),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("同步收藏夹或合集, 只下载新的视频")
+                .arg(url())
+                .arg(sync_removed())
+                .arg(transcode())
+                .arg(resume_download())
+                .arg(output_dir()),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("合并下载的DASH视频和音频 (.video / .audio) 为MP4")
+                .arg(arg!(<merge_video>).required(true).help("视频文件"))
+                .arg(arg!(<merge_audio>).required(true).help("音频文件"))
+                .arg(arg!(<merge_output>).required(true).help("输出的MP4文件"))
+                .arg(transcode()),
+        )
+        .subcommand(
+            Command::new("tui")
+                .about("全屏的终端界面, 浏览收藏夹/稍后再看/关注的UP主/追番并下载")
+                .arg(output_dir()),
+        )
+        .subcommand(
+            Command::new("play")
+                .about("不下载, 直接使用播放器(默认为mpv)播放视频")
+                .arg(url())
+                .arg(danmaku())
+                .arg(subtitles()),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("本地HTTP服务, 让局域网中的电视和播放器不需要设置referer就可以播放视频")
+                .arg(serve_listen()),
         )
 }
 
@@ -45,6 +184,154 @@ pub(crate) fn subcommand() -> Option<String> {
     }
 }
 
+/// 使用的账号, 所有子命令都可以使用
+pub(crate) fn profile() -> Arg {
+    arg!(<profile>)
+        .long("profile")
+        .required(false)
+        .global(true)
+        .help("使用的账号, 不指定时使用默认账号")
+        .value_parser(profile_name_v)
+}
+
+fn profile_name_v(profile: &str) -> Result<String, String> {
+    if !profile.is_empty()
+        && profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(profile.to_string())
+    } else {
+        Err("账号名称只能包含字母, 数字, - 和 _".to_string())
+    }
+}
+
+/// 获取账号参数的值
+pub(crate) fn profile_value() -> Option<String> {
+    let mut matches = args();
+    while let Some((_, sub_matches)) = matches.subcommand() {
+        matches = sub_matches;
+    }
+    matches.get_one::<String>("profile").cloned()
+}
+
+/// 账号管理中的账号名称
+pub(crate) fn profile_name() -> Arg {
+    arg!(<profile_name>)
+        .required(true)
+        .help("账号名称")
+        .value_parser(profile_name_v)
+}
+
+/// 二级子命令, 例如 profile list
+pub(crate) fn nested_subcommand() -> Option<String> {
+    if let Some((str, _)) = args().subcommand().unwrap().1.subcommand() {
+        Some(str.to_string())
+    } else {
+        None
+    }
+}
+
+fn nested_args() -> &'static ArgMatches {
+    args().subcommand().unwrap().1.subcommand().unwrap().1
+}
+
+pub(crate) fn profile_name_value() -> String {
+    nested_args()
+        .get_one::<String>("profile_name")
+        .unwrap()
+        .to_string()
+}
+
+/// 合并音频和视频的方式, 所有子命令都可以使用
+pub(crate) fn muxer() -> Arg {
+    arg!(<muxer>)
+        .long("muxer")
+        .required(false)
+        .global(true)
+        .value_parser(["auto", "ffmpeg", "libav", "native"])
+        .help("合并音频和视频的方式, 不指定时使用配置中的muxer")
+}
+
+pub(crate) fn muxer_value() -> Option<String> {
+    let mut matches = args();
+    while let Some((_, sub_matches)) = matches.subcommand() {
+        matches = sub_matches;
+    }
+    matches.get_one::<String>("muxer").cloned()
+}
+
+/// 本次运行时覆盖配置, 所有子命令都可以使用
+pub(crate) fn config_override() -> Arg {
+    arg!(<config_override>)
+        .long("set")
+        .required(false)
+        .global(true)
+        .action(ArgAction::Append)
+        .help("覆盖配置, 格式为 key=value, 可以使用多次")
+        .value_parser(config_override_v)
+}
+
+fn config_override_v(value: &str) -> Result<String, String> {
+    match value.split_once('=') {
+        Some((key, v)) => {
+            crate::config::validate_config(key, v)?;
+            Ok(value.to_string())
+        }
+        None => Err("格式为 key=value".to_string()),
+    }
+}
+
+/// 获取命令行中覆盖的配置
+pub(crate) fn config_override_value(key: &str) -> Option<String> {
+    let mut matches = args();
+    while let Some((_, sub_matches)) = matches.subcommand() {
+        matches = sub_matches;
+    }
+    matches
+        .get_many::<String>("config_override")?
+        .filter_map(|value| value.split_once('='))
+        .filter(|(k, _)| *k == key)
+        .map(|(_, v)| v.to_string())
+        .last()
+}
+
+/// 配置的名称
+pub(crate) fn config_key() -> Arg {
+    arg!(<config_key>).required(true).help("配置的名称")
+}
+
+pub(crate) fn config_key_value() -> String {
+    nested_args()
+        .get_one::<String>("config_key")
+        .unwrap()
+        .to_string()
+}
+
+/// 配置的值
+pub(crate) fn config_value() -> Arg {
+    arg!(<config_value>).required(true).help("配置的值")
+}
+
+pub(crate) fn config_value_value() -> String {
+    nested_args()
+        .get_one::<String>("config_value")
+        .unwrap()
+        .to_string()
+}
+
+/// 加密登录信息时使用的密钥文件
+pub(crate) fn key_file() -> Arg {
+    arg!(<key_file>)
+        .long("key-file")
+        .required(false)
+        .help("使用密钥文件代替密码, 不指定时需要输入密码")
+}
+
+pub(crate) fn key_file_value() -> Option<String> {
+    nested_args().get_one::<String>("key_file").cloned()
+}
+
 /// 控制台输出二维码参数
 pub(crate) fn qr_console() -> Arg {
     arg!(<console_qrcode>)
@@ -55,8 +342,98 @@ pub(crate) fn qr_console() -> Arg {
 }
 
 /// 控制台输出二维码参数
+/// 在login以外的子命令中重新登录时为false
 pub(crate) fn qr_console_value() -> bool {
-    args().subcommand().unwrap().1.get_flag("console_qrcode")
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("console_qrcode")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 扫码登录超时时间
+pub(crate) fn login_timeout() -> Arg {
+    arg!(<login_timeout>)
+        .long("timeout")
+        .required(false)
+        .default_value("180")
+        .help("扫码登录的超时时间(秒), 二维码过期时会自动重新生成")
+        .value_parser(clap::value_parser!(u64).range(1..))
+}
+
+/// 在login以外的子命令中重新登录时使用默认值
+pub(crate) fn login_timeout_value() -> u64 {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<u64>("login_timeout")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(180)
+}
+
+/// 从cookies.txt登录
+pub(crate) fn cookies() -> Arg {
+    arg!(<cookies>)
+        .long("cookies")
+        .required(false)
+        .conflicts_with("sess_data")
+        .help("使用浏览器导出的cookie文件登录 (Netscape格式的cookies.txt, 或者JSON格式)")
+}
+
+pub(crate) fn cookies_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("cookies")
+        .ok()
+        .flatten()
+        .cloned()
+}
+
+/// 使用SESSDATA登录
+pub(crate) fn sess_data() -> Arg {
+    arg!(<sess_data>)
+        .long("sessdata")
+        .required(false)
+        .help("使用浏览器cookie中SESSDATA的值登录")
+}
+
+pub(crate) fn sess_data_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("sess_data")
+        .ok()
+        .flatten()
+        .cloned()
+}
+
+/// 刷新cookie用的refresh_token
+pub(crate) fn refresh_token() -> Arg {
+    arg!(<refresh_token>)
+        .long("refresh-token")
+        .required(false)
+        .help("浏览器localStorage中ac_time_value的值, 使用cookie登录时指定后可以自动刷新登录信息")
+}
+
+pub(crate) fn refresh_token_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("refresh_token")
+        .ok()
+        .flatten()
+        .cloned()
 }
 
 /// 格式参数, 下载bv的时候可以指定格式
@@ -66,8 +443,7 @@ pub(crate) fn format() -> Arg {
         .short('f')
         .long("format")
         .required(false)
-        .default_value("choose")
-        .help("视频格式 只能为 mp4/dash/choose 其中之一")
+        .help("视频格式 只能为 mp4/dash/choose 其中之一, 不指定时使用配置中的format")
         .value_parser(format_v)
 }
 
@@ -82,13 +458,15 @@ fn format_v(format: &str) -> Result<String, String> {
 
 /// 获取格式的值
 pub(crate) fn format_value() -> &'static str {
-    let format_string = args()
-        .subcommand()
-        .unwrap()
-        .1
-        .get_one::<String>("format")
-        .unwrap();
-    let mut format_str: &str = format_string.as_str();
+    let format_string = match args().subcommand().unwrap().1.get_one::<String>("format") {
+        Some(format_string) => format_string.clone(),
+        None => crate::config::config_value("format"),
+    };
+    let mut format_str: &str = match format_string.as_str() {
+        "mp4" => "mp4",
+        "dash" => "dash",
+        _ => "choose",
+    };
     if "choose" == format_str {
         format_str = ["dash", "mp4"][Select::new()
             .with_prompt("选择视频格式")
@@ -114,6 +492,17 @@ pub(crate) fn url() -> Arg {
     arg!(<url>).required(false).help("需要下载的url")
 }
 
+/// 合并的 (视频, 音频, 输出) 文件
+pub(crate) fn merge_files_value() -> (String, String, String) {
+    let matches = args().subcommand().unwrap().1;
+    let value = |id: &str| matches.get_one::<String>(id).unwrap().to_string();
+    (
+        value("merge_video"),
+        value("merge_audio"),
+        value("merge_output"),
+    )
+}
+
 /// 获取URL参数的值
 pub(crate) fn url_value() -> String {
     let url: &str = if let Some(str) = args().subcommand().unwrap().1.get_one::<String>("url") {
@@ -130,19 +519,14 @@ pub(crate) fn url_value() -> String {
     url.to_string()
 }
 
-/// 下载的url, 如果指定的次参数则不需要再输入
+/// 番剧的信息都通过ss/ep的id获取, 保留参数以兼容旧的命令
 pub(crate) fn parse_input_url() -> Arg {
     arg!(<parse_input_url>)
         .long("parse-input-url")
         .required(false)
         .action(ArgAction::SetTrue)
-        .help(
-            "使用url解析剧集数据而不是id, 有的剧集下不了加上这个试试 (对集合类视频有效，对BV无效)",
-        )
-}
-
-pub(crate) fn parse_input_url_value() -> bool {
-    args().subcommand().unwrap().1.get_flag("parse_input_url")
+        .hide(true)
+        .help("已不再需要, ss和ep开头的id都可以直接解析")
 }
 
 /// 获取EP
@@ -155,7 +539,199 @@ pub(crate) fn choose_seasons() -> Arg {
 }
 
 pub(crate) fn choose_seasons_value() -> bool {
-    args().subcommand().unwrap().1.get_flag("choose_seasons")
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("choose_seasons")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 选择要下载的剧集
+pub(crate) fn choose_episodes() -> Arg {
+    arg!(<choose_episodes>)
+        .long("choose-episodes")
+        .required(false)
+        .action(ArgAction::SetTrue)
+        .help("加上这个可以在每一季中选择要下载的剧集")
+}
+
+pub(crate) fn choose_episodes_value() -> bool {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("choose_episodes")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 下载番剧时的剧集范围
+pub(crate) fn episodes() -> Arg {
+    arg!(<episodes>)
+        .long("episodes")
+        .required(false)
+        .value_parser(ranges_v)
+        .help("下载番剧时只下载这些剧集, 从1开始, 例如 1-12,15 或 13-")
+}
+
+pub(crate) fn episodes_value() -> Option<Vec<(usize, usize)>> {
+    ranges_value("episodes")
+}
+
+/// 下载合集/收藏夹/UP主投稿时的视频范围
+pub(crate) fn items() -> Arg {
+    arg!(<items>)
+        .long("items")
+        .required(false)
+        .value_parser(ranges_v)
+        .help("下载合集, 收藏夹或UP主投稿时只下载这些视频, 按照页面上的顺序从1开始, 例如 1-20,25")
+}
+
+pub(crate) fn items_value() -> Option<Vec<(usize, usize)>> {
+    ranges_value("items")
+}
+
+/// 格式为 1-12,15 或者 13- 的范围, 结束为空时表示到最后
+fn ranges_v(value: &str) -> Result<Vec<(usize, usize)>, String> {
+    let error = || format!("范围的格式错误, 例如 1-12,15 或 13- : {}", value);
+    let number = |str: &str| match str.trim().parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(error()),
+    };
+    let mut ranges = vec![];
+    for part in value.split(',') {
+        let range = match part.split_once('-') {
+            Some((begin, end)) if end.trim().is_empty() => (number(begin)?, usize::MAX),
+            Some((begin, end)) => (number(begin)?, number(end)?),
+            None => (number(part)?, number(part)?),
+        };
+        if range.0 > range.1 {
+            return Err(error());
+        }
+        ranges.push(range);
+    }
+    Ok(ranges)
+}
+
+fn ranges_value(id: &str) -> Option<Vec<(usize, usize)>> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<Vec<(usize, usize)>>(id)
+        .ok()
+        .flatten()
+        .cloned()
+}
+
+/// 只下载最新的几个
+pub(crate) fn latest() -> Arg {
+    arg!(<latest>)
+        .long("latest")
+        .required(false)
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .help("只下载最新的N集或N个视频, 和 --episodes / --items 一起使用时在范围中选择最新的")
+}
+
+pub(crate) fn latest_value() -> Option<usize> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<u64>("latest")
+        .ok()
+        .flatten()
+        .map(|latest| *latest as usize)
+}
+
+/// 下载番剧时同时下载正片以外的内容
+pub(crate) fn extras() -> Arg {
+    arg!(<extras>)
+        .long("extras")
+        .required(false)
+        .num_args(0..=1)
+        .default_missing_value("all")
+        .value_parser(["all", "choose"])
+        .help("同时下载番剧中正片以外的内容 (PV / SP / OVA 等), 使用 --extras choose 可以选择要下载的内容")
+}
+
+pub(crate) fn extras_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("extras")
+        .ok()
+        .flatten()
+        .cloned()
+}
+
+/// 保存封面
+pub(crate) fn cover() -> Arg {
+    arg!(<cover>)
+        .long("cover")
+        .required(false)
+        .action(ArgAction::SetTrue)
+        .help("同时保存视频的封面, 番剧每一季的海报, 以及合集和收藏夹的封面")
+}
+
+pub(crate) fn cover_value() -> bool {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("cover")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 合并后生成缩略图
+pub(crate) fn thumbnails() -> Arg {
+    arg!(<thumbnails>)
+        .long("thumbnails")
+        .required(false)
+        .num_args(0..=1)
+        .default_missing_value("sheet")
+        .value_parser(["sheet", "chapters"])
+        .help("合并后使用ffmpeg生成缩略图, sheet: 4x4的预览图(默认), chapters: 每个章节一张图片, 没有章节时生成预览图")
+}
+
+pub(crate) fn thumbnails_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("thumbnails")
+        .ok()
+        .flatten()
+        .cloned()
+}
+
+/// 下载后转码
+pub(crate) fn transcode() -> Arg {
+    arg!(<transcode>)
+        .long("transcode")
+        .required(false)
+        .help("下载后使用的转码配置, 内置 h264-1080p/hevc-small/av1-archive, 不指定时使用配置中的transcode")
+}
+
+pub(crate) fn transcode_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("transcode")
+        .ok()
+        .flatten()
+        .cloned()
 }
 
 /// 断点续传
@@ -169,5 +745,349 @@ pub(crate) fn resume_download() -> Arg {
 }
 
 pub(crate) fn resume_download_value() -> bool {
-    args().subcommand().unwrap().1.get_flag("resume_download")
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("resume_download")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 同步时来源中已经删除的视频的处理方式
+pub(crate) fn sync_removed() -> Arg {
+    arg!(<sync_removed>)
+        .long("removed")
+        .required(false)
+        .default_value("keep")
+        .value_parser(["keep", "archive", "delete"])
+        .help("来源中已经删除的视频: keep 保留 / archive 移动到\"已移除\"文件夹 / delete 删除")
+}
+
+pub(crate) fn sync_removed_value() -> String {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<String>("sync_removed")
+        .unwrap()
+        .to_string()
+}
+
+/// 番剧
+pub(crate) fn season() -> Arg {
+    arg!(<season>)
+        .required(true)
+        .help("番剧的url或者ss/ep开头的id")
+}
+
+pub(crate) fn season_value() -> String {
+    nested_args()
+        .get_one::<String>("season")
+        .unwrap()
+        .to_string()
+}
+
+/// 订阅的url
+pub(crate) fn subscription_url() -> Arg {
+    arg!(<subscription_url>)
+        .required(true)
+        .help("UP主空间/收藏夹/合集/番剧的url")
+}
+
+pub(crate) fn subscription_url_value() -> String {
+    nested_args()
+        .get_one::<String>("subscription_url")
+        .unwrap()
+        .to_string()
+}
+
+/// 订阅检查的间隔
+pub(crate) fn watch_interval() -> Arg {
+    arg!(<watch_interval>)
+        .long("interval")
+        .required(false)
+        .default_value("0")
+        .value_parser(clap::value_parser!(i64).range(0..))
+        .help("检查的间隔(秒), 不指定时使用配置中的watch_interval")
+}
+
+pub(crate) fn watch_interval_value() -> i64 {
+    *nested_args().get_one::<i64>("watch_interval").unwrap()
+}
+
+/// 只检查一次
+pub(crate) fn watch_once() -> Arg {
+    arg!(<watch_once>)
+        .long("once")
+        .required(false)
+        .action(ArgAction::SetTrue)
+        .help("检查一次全部到了间隔的订阅后退出")
+}
+
+pub(crate) fn watch_once_value() -> bool {
+    args().subcommand().unwrap().1.get_flag("watch_once")
+}
+
+/// 日志文件
+pub(crate) fn log_file() -> Arg {
+    arg!(<log_file>)
+        .long("log-file")
+        .required(false)
+        .help("日志文件, 不指定时保存在数据目录中的watch.log")
+}
+
+pub(crate) fn log_file_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<String>("log_file")
+        .cloned()
+}
+
+/// 下载到的文件夹
+pub(crate) fn output_dir() -> Arg {
+    arg!(<output_dir>)
+        .short('d')
+        .long("output-dir")
+        .required(false)
+        .help("下载到的文件夹, 不指定时使用配置中的output_dir, 都没有时使用当前文件夹")
+}
+
+pub(crate) fn output_dir_value() -> Option<String> {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<String>("output_dir")
+        .ok()
+        .flatten()
+        .cloned()
+}
+
+/// 搜索关键字
+pub(crate) fn keyword() -> Arg {
+    arg!(<keyword>).required(false).help("搜索关键字")
+}
+
+/// 获取搜索关键字, 没有指定时需要输入
+pub(crate) fn keyword_value() -> String {
+    if let Some(str) = args().subcommand().unwrap().1.get_one::<String>("keyword") {
+        if !str.is_empty() {
+            return str.to_string();
+        }
+    }
+    Input::new()
+        .with_prompt("请输入搜索关键字")
+        .interact_text()
+        .unwrap()
+}
+
+/// 搜索类型
+pub(crate) fn search_type() -> Arg {
+    arg!(<search_type>)
+        .short('t')
+        .long("type")
+        .required(false)
+        .default_value("video")
+        .help("搜索类型 只能为 video/bangumi/user 其中之一")
+        .value_parser(search_type_v)
+}
+
+fn search_type_v(search_type: &str) -> Result<String, String> {
+    match search_type {
+        "video" | "bangumi" | "user" => Ok(search_type.to_string()),
+        _ => Err("搜索类型 只能为 video/bangumi/user 其中之一".to_string()),
+    }
+}
+
+pub(crate) fn search_type_value() -> &'static str {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<String>("search_type")
+        .unwrap()
+        .as_str()
+}
+
+/// 搜索结果排序
+pub(crate) fn search_order() -> Arg {
+    arg!(<search_order>)
+        .short('o')
+        .long("order")
+        .required(false)
+        .default_value("totalrank")
+        .help("排序 视频: totalrank(综合)/click(播放)/pubdate(最新)/dm(弹幕)/stow(收藏), 用户: totalrank/fans(粉丝)/level(等级), 番剧: totalrank")
+        .value_parser(search_order_v)
+}
+
+fn search_order_v(order: &str) -> Result<String, String> {
+    match order {
+        "totalrank" | "click" | "pubdate" | "dm" | "stow" | "fans" | "level" => {
+            Ok(order.to_string())
+        }
+        _ => Err("排序 只能为 totalrank/click/pubdate/dm/stow/fans/level 其中之一".to_string()),
+    }
+}
+
+/// 每种搜索类型可以使用的排序
+fn search_orders(search_type: &str) -> &'static [&'static str] {
+    match search_type {
+        "video" => &["totalrank", "click", "pubdate", "dm", "stow"],
+        "user" => &["totalrank", "fans", "level"],
+        _ => &["totalrank"],
+    }
+}
+
+/// 检查排序是否可以用于搜索类型
+pub(crate) fn check_search_order(search_type: &str, order: &str) -> crate::Result<()> {
+    let orders = search_orders(search_type);
+    if orders.contains(&order) {
+        return Ok(());
+    }
+    Err(anyhow::Error::msg(format!(
+        "搜索类型 {} 不能使用排序 {}, 只能为 {} 其中之一",
+        search_type,
+        order,
+        orders.join("/")
+    )))
+}
+
+pub(crate) fn search_order_value() -> &'static str {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<String>("search_order")
+        .unwrap()
+        .as_str()
+}
+
+/// 视频时长筛选
+pub(crate) fn search_duration() -> Arg {
+    arg!(<search_duration>)
+        .long("duration")
+        .required(false)
+        .default_value("0")
+        .help("视频时长 0:全部 1:10分钟以下 2:10-30分钟 3:30-60分钟 4:60分钟以上 (只对视频有效)")
+        .value_parser(clap::value_parser!(i64).range(0..=4))
+}
+
+pub(crate) fn search_duration_value() -> i64 {
+    *args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<i64>("search_duration")
+        .unwrap()
+}
+
+/// 从第几页开始
+pub(crate) fn search_page() -> Arg {
+    arg!(<search_page>)
+        .short('p')
+        .long("page")
+        .required(false)
+        .default_value("1")
+        .help("从第几页开始显示")
+        .value_parser(clap::value_parser!(i64).range(1..))
+}
+
+pub(crate) fn search_page_value() -> i64 {
+    *args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<i64>("search_page")
+        .unwrap()
+}
+
+/// 播放时加载弹幕
+pub(crate) fn danmaku() -> Arg {
+    arg!(<danmaku>)
+        .long("danmaku")
+        .required(false)
+        .action(ArgAction::SetTrue)
+        .help("将弹幕转换为ASS字幕并加载到播放器")
+}
+
+pub(crate) fn danmaku_value() -> bool {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("danmaku")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 播放时加载字幕
+pub(crate) fn subtitles() -> Arg {
+    arg!(<subtitles>)
+        .long("subtitles")
+        .required(false)
+        .action(ArgAction::SetTrue)
+        .help("加载视频的字幕(CC字幕), 有多种语言时优先使用非AI生成的字幕")
+}
+
+pub(crate) fn subtitles_value() -> bool {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .try_get_one::<bool>("subtitles")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// 本地HTTP服务监听的地址
+pub(crate) fn serve_listen() -> Arg {
+    arg!(<serve_listen>)
+        .long("listen")
+        .required(false)
+        .default_value("127.0.0.1:8090")
+        .help("监听的地址, 使用 0.0.0.0:8090 让局域网中的设备可以访问")
+}
+
+pub(crate) fn serve_listen_value() -> String {
+    args()
+        .subcommand()
+        .unwrap()
+        .1
+        .get_one::<String>("serve_listen")
+        .unwrap()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(ranges_v("1-12,15"), Ok(vec![(1, 12), (15, 15)]));
+        assert_eq!(ranges_v(" 3 - 5 "), Ok(vec![(3, 5)]));
+        // 结束为空时到最后
+        assert_eq!(ranges_v("13-"), Ok(vec![(13, usize::MAX)]));
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        // 从1开始
+        assert!(ranges_v("0").is_err());
+        assert!(ranges_v("0-3").is_err());
+        // 开始大于结束
+        assert!(ranges_v("5-3").is_err());
+        assert!(ranges_v("-3").is_err());
+        assert!(ranges_v("1,,2").is_err());
+        assert!(ranges_v("a").is_err());
+    }
 }
@@ -0,0 +1,241 @@
+use anyhow::Context;
+use chrono::{Local, TimeZone};
+
+use crate::api::WebApi;
+use crate::config::config_value;
+use crate::down::{down_episode, episode_name, season_dir_name, series_dir, SERIES_PATTERN};
+use crate::entities::tracked_season;
+use crate::local::{delete_tracked_season, join_paths, load_tracked_seasons, save_tracked_season};
+use crate::region::Region;
+use crate::{app, login_client, web_api};
+
+/// 追番
+pub(crate) async fn bangumi() -> crate::Result<()> {
+    match app::nested_subcommand() {
+        Some(subcommand) => match subcommand.as_str() {
+            "follow" => follow(app::season_value()).await?,
+            "unfollow" => unfollow(app::season_value()).await?,
+            "list" => list().await?,
+            "update" => update().await?,
+            _ => app::print_help()?,
+        },
+        None => list().await?,
+    }
+    Ok(())
+}
+
+/// 番剧的url或者ss/ep开头的id
+fn season_id_value(input: &str) -> crate::Result<String> {
+    match SERIES_PATTERN.find(input) {
+        Some(find) => Ok(find.as_str().to_owned()),
+        None => Err(anyhow::Error::msg(format!(
+            "无法识别的番剧, 请使用番剧的url或者ss/ep开头的id : {}",
+            input
+        ))),
+    }
+}
+
+async fn follow(input: String) -> crate::Result<()> {
+    let web_api = web_api().await?;
+    let season = web_api
+        .pgc_season(
+            config_value("api_endpoint").as_str(),
+            season_id_value(input.as_str())?.as_str(),
+        )
+        .await?;
+    let tracked = load_tracked_seasons().await?;
+    if tracked.iter().any(|x| x.season_id == season.season_id) {
+        println!("已经在追番 : {}", season.title);
+        return Ok(());
+    }
+    save_tracked_season(tracked_season::Model {
+        season_id: season.season_id,
+        title: season.title.clone(),
+        last_episode_id: 0,
+        updated_at: 0,
+    })
+    .await?;
+    println!(
+        "已追番 : {} (ss{}), 现在共 {} 集, 使用 bili-cli bangumi update 下载",
+        season.title,
+        season.season_id,
+        season.episodes.len()
+    );
+    Ok(())
+}
+
+async fn unfollow(input: String) -> crate::Result<()> {
+    let season_id = season_id_of(&web_api().await?, input.as_str()).await?;
+    if delete_tracked_season(season_id).await? {
+        println!("已取消追番 : ss{}", season_id);
+    } else {
+        println!("没有在追番 : ss{}", season_id);
+    }
+    Ok(())
+}
+
+/// 番剧的season_id, ep开头时需要查询所在的season
+async fn season_id_of(web_api: &WebApi, input: &str) -> crate::Result<i64> {
+    let id = season_id_value(input)?;
+    Ok(match id.strip_prefix("ss") {
+        Some(season_id) => season_id.parse()?,
+        None => {
+            web_api
+                .pgc_season(config_value("api_endpoint").as_str(), id.as_str())
+                .await?
+                .season_id
+        }
+    })
+}
+
+async fn list() -> crate::Result<()> {
+    let tracked = load_tracked_seasons().await?;
+    if tracked.is_empty() {
+        println!("没有追番, 请使用 bili-cli bangumi follow <url> 添加");
+        return Ok(());
+    }
+    for item in tracked {
+        let updated_at = if item.updated_at == 0 {
+            "未更新".to_owned()
+        } else {
+            Local
+                .timestamp_opt(item.updated_at, 0)
+                .single()
+                .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        println!(
+            "ss{} {} (上次更新 : {})",
+            item.season_id, item.title, updated_at
+        );
+    }
+    Ok(())
+}
+
+/// 下载追番中新的剧集, 跳过预告和不能观看的付费剧集
+/// 遇到跳过的付费剧集或者下载失败时, 之后的剧集在下次更新时会再检查一次
+pub(crate) async fn update() -> crate::Result<()> {
+    let web_api = login_client().await?;
+    let is_vip = web_api.nav().await.map(|nav| nav.is_vip()).unwrap_or(false);
+    for tracked in load_tracked_seasons().await? {
+        println!();
+        let title = tracked.title.clone();
+        match update_tracked(&web_api, is_vip, tracked).await {
+            Ok(count) => println!("{} : 下载了 {} 集", title, count),
+            Err(err) => println!("{} : 更新失败 : {}", title, err),
+        }
+    }
+    Ok(())
+}
+
+/// 订阅的番剧和追番使用相同的方式更新, 没有追番时先追番
+pub(crate) async fn update_subscription(web_api: &WebApi, url: &str) -> crate::Result<()> {
+    let season_id = season_id_of(web_api, url).await?;
+    let tracked = load_tracked_seasons()
+        .await?
+        .into_iter()
+        .find(|x| x.season_id == season_id)
+        .unwrap_or(tracked_season::Model {
+            season_id,
+            title: String::new(),
+            last_episode_id: 0,
+            updated_at: 0,
+        });
+    let is_vip = web_api.nav().await.map(|nav| nav.is_vip()).unwrap_or(false);
+    let count = update_tracked(web_api, is_vip, tracked).await?;
+    println!("下载了 {} 集", count);
+    Ok(())
+}
+
+/// 更新一部追番, 失败时也保存更新的时间和已经下载到的剧集
+async fn update_tracked(
+    web_api: &WebApi,
+    is_vip: bool,
+    mut tracked: tracked_season::Model,
+) -> crate::Result<usize> {
+    let result = update_season(web_api, is_vip, &mut tracked).await;
+    tracked.updated_at = Local::now().timestamp();
+    save_tracked_season(tracked).await?;
+    result
+}
+
+/// 和下载番剧使用相同的文件夹和文件名, 已经下载的剧集会被跳过
+async fn update_season(
+    web_api: &WebApi,
+    is_vip: bool,
+    tracked: &mut tracked_season::Model,
+) -> crate::Result<usize> {
+    let season = web_api
+        .pgc_season(
+            config_value("api_endpoint").as_str(),
+            format!("ss{}", tracked.season_id).as_str(),
+        )
+        .await?;
+    tracked.title = season.title.clone();
+    let region = Region::of_season(&season);
+    let season_info = season
+        .season_list()
+        .into_iter()
+        .find(|x| x.season_id == tracked.season_id)
+        .with_context(|| format!("未找到番剧 : ss{}", tracked.season_id))?;
+    let start = season
+        .episodes
+        .iter()
+        .position(|ep| ep.id == tracked.last_episode_id)
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    println!(
+        "{} : 共 {} 集, 新的剧集 {} 集",
+        season.title,
+        season.episodes.len(),
+        season.episodes.len() - start
+    );
+    let folder = join_paths(vec![
+        series_dir(&season).as_str(),
+        season_dir_name(&season_info, &season).as_str(),
+    ]);
+    std::fs::create_dir_all(folder.as_str())?;
+    let mut count = 0;
+    // 前面的剧集都已经下载或者跳过时, 才记录为已经下载到的剧集
+    let mut advance = true;
+    for (index, ep) in season.episodes.iter().enumerate().skip(start) {
+        if ep.is_preview() {
+            println!("  跳过预告 : {} {}", ep.title, ep.long_title);
+            if advance {
+                tracked.last_episode_id = ep.id;
+            }
+            continue;
+        }
+        if ep.is_paid() || (ep.requires_vip() && !is_vip) {
+            println!(
+                "  跳过 ({}) : {} {}",
+                if ep.is_paid() {
+                    "需要购买"
+                } else {
+                    "需要大会员"
+                },
+                ep.title,
+                ep.long_title
+            );
+            advance = false;
+            continue;
+        }
+        // 文件名使用下载番剧时的序号和标题
+        let name = episode_name(index, ep);
+        println!();
+        println!("{}", name);
+        match down_episode(web_api, region, ep.aid, &ep.bvid, ep.cid, &folder, &name).await {
+            Ok(_) => {
+                count += 1;
+                if advance {
+                    tracked.last_episode_id = ep.id;
+                }
+            }
+            Err(err) => {
+                println!("  下载失败 : {}", err);
+                advance = false;
+            }
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use crate::api::ViewPoint;
+use crate::down::print_line;
+use crate::{app, config, web_api};
+
+/// 视频的章节, 来自UP主设置的分段 (view points)
+#[derive(Clone)]
+pub(crate) struct Chapter {
+    pub title: String,
+    /// 开始和结束的时间(毫秒)
+    pub start: u64,
+    pub end: u64,
+}
+
+/// 是否将章节写入合并后的视频
+pub(crate) fn embed_chapters() -> bool {
+    matches!(config::config_value("chapters").as_str(), "embed" | "both")
+}
+
+/// 是否将章节保存为单独的文件
+pub(crate) fn save_chapters_txt() -> bool {
+    matches!(config::config_value("chapters").as_str(), "file" | "both")
+}
+
+/// 是否需要读取章节, 合并时写入视频或者保存为单独的文件
+pub(crate) fn need_chapters() -> bool {
+    config::config_value("chapters") != "none"
+}
+
+/// 读取视频的分段 (view points), 章节和章节缩略图 (--thumbnails chapters) 共用, 只请求一次
+/// 不需要章节并且不生成章节缩略图时不读取, 读取失败时为None
+pub(crate) async fn fetch_view_points(
+    bvid: &str,
+    cid: i64,
+    with_chapters: bool,
+) -> Option<Vec<ViewPoint>> {
+    let with_thumbnails = app::thumbnails_value().as_deref() == Some("chapters");
+    if !with_chapters && !with_thumbnails {
+        return Some(vec![]);
+    }
+    let result = match web_api().await {
+        Ok(web_api) => web_api.player_info(bvid, cid).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(info) => Some(info.view_points),
+        Err(err) => {
+            print_line(format!(" > 未能读取章节 : {}", err));
+            None
+        }
+    }
+}
+
+/// 分段转换为章节, 不需要章节或者读取失败时为空
+pub(crate) fn chapters(view_points: Option<&[ViewPoint]>) -> Vec<Chapter> {
+    if !need_chapters() {
+        return vec![];
+    }
+    let chapters: Vec<Chapter> = view_points
+        .unwrap_or_default()
+        .iter()
+        .filter(|point| point.to > point.from)
+        .map(|point| Chapter {
+            title: point.content.clone(),
+            start: point.from as u64 * 1000,
+            end: point.to as u64 * 1000,
+        })
+        .collect();
+    if !chapters.is_empty() {
+        print_line(format!(" > 章节 : {} 个", chapters.len()));
+    }
+    chapters
+}
+
+/// 按照配置将章节保存为和视频同名的 .chapters.txt, 每行为 时:分:秒 标题
+pub(crate) fn save_chapters_file(chapters: &[Chapter], file: &str) {
+    if chapters.is_empty() || !save_chapters_txt() {
+        return;
+    }
+    let txt = format!("{}.chapters.txt", file.trim_end_matches(".mp4"));
+    if Path::new(&txt).exists() {
+        return;
+    }
+    let content: String = chapters
+        .iter()
+        .map(|chapter| {
+            let seconds = chapter.start / 1000;
+            format!(
+                "{:02}:{:02}:{:02} {}\n",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60,
+                chapter.title
+            )
+        })
+        .collect();
+    match std::fs::write(txt.as_str(), content) {
+        Ok(_) => print_line(format!(" > 保存章节 : {}", txt)),
+        Err(err) => print_line(format!(" > 未能保存章节 : {}", err)),
+    }
+}
+
+/// ffmpeg的元数据文件, 使用 -map_chapters 写入章节
+pub(crate) fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut content = ";FFMETADATA1\n".to_owned();
+    for chapter in chapters {
+        content.push_str("[CHAPTER]\nTIMEBASE=1/1000\n");
+        content.push_str(format!("START={}\nEND={}\n", chapter.start, chapter.end).as_str());
+        content.push_str(format!("title={}\n", escape_ffmetadata(&chapter.title)).as_str());
+    }
+    content
+}
+
+/// 元数据文件中 = ; # \ 和换行需要转义
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for char in value.chars() {
+        if matches!(char, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
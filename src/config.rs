@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+
+use crate::local::{
+    cfg_local_dir, delete_property, init_dir, join_paths, load_properties_with_prefix,
+    lock_properties, save_property, unlock_properties,
+};
+use crate::{app, profile};
+
+/// 配置项
+struct ConfigItem {
+    key: &'static str,
+    default: &'static str,
+    help: &'static str,
+    validator: fn(&str) -> Result<(), String>,
+}
+
+const CONFIG_ITEMS: &[ConfigItem] = &[
+    ConfigItem {
+        key: "format",
+        default: "choose",
+        help: "默认视频格式 mp4/dash/choose",
+        validator: validate_format,
+    },
+    ConfigItem {
+        key: "video_quality",
+        default: "choose",
+        help: "视频清晰度 choose(下载单个视频时选择)/best(最高)/清晰度代码(例如 80 为1080P, 选择不超过这个清晰度的最高清晰度)",
+        validator: validate_quality,
+    },
+    ConfigItem {
+        key: "audio_quality",
+        default: "choose",
+        help: "音频质量 choose/best/音频代码(例如 30280 为192K)",
+        validator: validate_quality,
+    },
+    ConfigItem {
+        key: "output_dir",
+        default: "",
+        help: "下载到的文件夹, 为空时使用当前文件夹",
+        validator: validate_any,
+    },
+    ConfigItem {
+        key: "filename_template",
+        default: "{title}",
+        help: "视频文件名模板, 可以使用 {title} {bvid}",
+        validator: validate_filename_template,
+    },
+    ConfigItem {
+        key: "concurrency",
+        default: "1",
+        help: "下载合集/收藏夹时同时下载的视频数",
+        validator: validate_positive_number,
+    },
+    ConfigItem {
+        key: "watch_interval",
+        default: "3600",
+        help: "watch检查订阅的间隔(秒), 订阅可以单独指定",
+        validator: validate_positive_number,
+    },
+    ConfigItem {
+        key: "proxy",
+        default: "",
+        help: "代理服务器, 例如 http://127.0.0.1:7890 或 socks5://127.0.0.1:1080",
+        validator: validate_proxy,
+    },
+    ConfigItem {
+        key: "api_endpoint",
+        default: "https://api.bilibili.com",
+        help: "获取番剧信息和播放地址的接口服务器",
+        validator: validate_url,
+    },
+    ConfigItem {
+        key: "hk_endpoint",
+        default: "",
+        help: "下载仅限港澳台地区的番剧时使用的接口服务器, 例如自建的代理服务器",
+        validator: validate_url,
+    },
+    ConfigItem {
+        key: "tw_endpoint",
+        default: "",
+        help: "下载仅限台湾地区的番剧时使用的接口服务器",
+        validator: validate_url,
+    },
+    ConfigItem {
+        key: "sea_endpoint",
+        default: "",
+        help: "下载仅限东南亚地区的番剧时使用的接口服务器",
+        validator: validate_url,
+    },
+    ConfigItem {
+        key: "user_agent",
+        default: "",
+        help: "请求时使用的User-Agent, 为空时使用内置的",
+        validator: validate_any,
+    },
+    ConfigItem {
+        key: "connect_timeout",
+        default: "10",
+        help: "连接超时(秒)",
+        validator: validate_positive_number,
+    },
+    ConfigItem {
+        key: "read_timeout",
+        default: "30",
+        help: "读取超时(秒), 下载时超过这个时间没有收到数据时失败",
+        validator: validate_positive_number,
+    },
+    ConfigItem {
+        key: "request_timeout",
+        default: "30",
+        help: "请求超时(秒), 接口和图片的请求超过这个时间没有完成时失败",
+        validator: validate_positive_number,
+    },
+    ConfigItem {
+        key: "ip_version",
+        default: "auto",
+        help: "使用IPv4或IPv6连接 auto/4/6",
+        validator: validate_ip_version,
+    },
+    ConfigItem {
+        key: "ca_file",
+        default: "",
+        help: "额外信任的CA证书(PEM格式)文件",
+        validator: validate_any,
+    },
+    ConfigItem {
+        key: "muxer",
+        default: "auto",
+        help: "合并音频和视频的方式 auto(按照 native/libav/ffmpeg 的顺序使用可用的方式)/ffmpeg(ffmpeg命令)/libav(链接的ffmpeg库)/native(内置的MP4封装)",
+        validator: validate_muxer,
+    },
+    ConfigItem {
+        key: "transcode",
+        default: "",
+        help: "下载后使用的转码配置, 内置 h264-1080p/hevc-small/av1-archive, 也可以在配置文件中使用 [transcode_profiles.名称] 添加, 为空时不转码, 转码使用ffmpeg命令行",
+        validator: validate_any,
+    },
+    ConfigItem {
+        key: "chapters",
+        default: "embed",
+        help: "视频的章节 embed(写入合并后的视频)/file(保存为单独的 .chapters.txt)/both(两者都要)/none(不读取)",
+        validator: validate_chapters,
+    },
+    ConfigItem {
+        key: "ffmpeg_path",
+        default: "ffmpeg",
+        help: "ffmpeg可执行文件的路径",
+        validator: validate_any,
+    },
+    ConfigItem {
+        key: "ffmpeg_timeout",
+        default: "600",
+        help: "ffmpeg超过这个时间(秒)没有进度时结束ffmpeg, 0为不限制",
+        validator: validate_number,
+    },
+    ConfigItem {
+        key: "player",
+        default: "mpv",
+        help: "play 使用的播放器, 支持 mpv/vlc, 其他播放器需要配置 player_args",
+        validator: validate_any,
+    },
+    ConfigItem {
+        key: "player_args",
+        default: "",
+        help: "播放器的参数, 以空格分隔, 可以使用 {video} {audio} {title} {referer} {user_agent} {subtitle}, 为空时使用 mpv/vlc 的参数",
+        validator: validate_any,
+    },
+];
+
+/// 配置的来源, 优先级从低到高
+#[derive(Clone, Copy)]
+enum ConfigSource {
+    Default,
+    File,
+    ProfileFile,
+    Property,
+    ProfileProperty,
+    Env,
+    Arg,
+}
+
+impl ConfigSource {
+    fn description(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "默认值",
+            ConfigSource::File => "配置文件",
+            ConfigSource::ProfileFile => "配置文件中的账号配置",
+            ConfigSource::Property => "config set",
+            ConfigSource::ProfileProperty => "账号的config set",
+            ConfigSource::Env => "环境变量",
+            ConfigSource::Arg => "命令行参数",
+        }
+    }
+}
+
+/// 配置文件和config set保存的配置
+struct Config {
+    file: toml::value::Table,
+    properties: HashMap<String, String>,
+    /// 当前使用的账号, 账号的配置优先于全局的配置
+    profile: String,
+}
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// 保存在配置表中的配置的前缀
+const PROPERTY_PREFIX: &str = "config.";
+
+/// 配置文件路径
+pub(crate) fn config_file() -> String {
+    join_paths(vec![cfg_local_dir().as_str(), "config.toml"])
+}
+
+/// 读取配置文件和config set保存的配置, 需要在读取配置之前调用
+pub(crate) async fn init_config() -> crate::Result<()> {
+    init_dir();
+    let path = config_file();
+    let file = if Path::new(path.as_str()).exists() {
+        let content = std::fs::read_to_string(path.as_str())?;
+        toml::from_str::<toml::value::Table>(content.as_str())
+            .map_err(|err| anyhow::Error::msg(format!("配置文件格式错误 : {} : {}", path, err)))?
+    } else {
+        toml::value::Table::new()
+    };
+    let properties = load_properties_with_prefix(PROPERTY_PREFIX)
+        .await?
+        .into_iter()
+        .map(|(k, v)| (k[PROPERTY_PREFIX.len()..].to_owned(), v))
+        .collect();
+    let profile = profile::active_profile().await?;
+    let _ = CONFIG.set(Config {
+        file,
+        properties,
+        profile,
+    });
+    Ok(())
+}
+
+fn env_name(key: &str) -> String {
+    format!("BILI_CLI_{}", key.to_uppercase())
+}
+
+fn toml_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(str) => str.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// config set保存的属性名, 指定了--profile时只对这个账号生效
+fn property_key(key: &str) -> String {
+    match app::profile_value() {
+        Some(profile) => format!("{}{}", PROPERTY_PREFIX, profile::profile_key(key, &profile)),
+        None => format!("{}{}", PROPERTY_PREFIX, key),
+    }
+}
+
+/// 取配置的值: 命令行参数 --set > 环境变量 > config set > 配置文件 > 默认值
+/// config set和配置文件中, 账号的配置 (配置文件中的 [profiles.账号名] ) 优先于全局的配置
+fn lookup(key: &str) -> (String, ConfigSource) {
+    if let Some(value) = app::config_override_value(key) {
+        return (value, ConfigSource::Arg);
+    }
+    if let Ok(value) = std::env::var(env_name(key)) {
+        return (value, ConfigSource::Env);
+    }
+    if let Some(config) = CONFIG.get() {
+        let profile_key = profile::profile_key(key, &config.profile);
+        if profile_key != key {
+            if let Some(value) = config.properties.get(&profile_key) {
+                return (value.clone(), ConfigSource::ProfileProperty);
+            }
+        }
+        if let Some(value) = config.properties.get(key) {
+            return (value.clone(), ConfigSource::Property);
+        }
+        if let Some(value) = config
+            .file
+            .get("profiles")
+            .and_then(|profiles| profiles.get(config.profile.as_str()))
+            .and_then(|profile| profile.get(key))
+            .filter(|value| !value.is_table())
+        {
+            return (toml_to_string(value), ConfigSource::ProfileFile);
+        }
+        // 表不是配置的值, 例如 [transcode_profiles]
+        if let Some(value) = config.file.get(key).filter(|value| !value.is_table()) {
+            return (toml_to_string(value), ConfigSource::File);
+        }
+    }
+    let default = CONFIG_ITEMS
+        .iter()
+        .find(|item| item.key == key)
+        .map(|item| item.default)
+        .unwrap_or_default();
+    (default.to_owned(), ConfigSource::Default)
+}
+
+/// 取配置的值
+pub(crate) fn config_value(key: &str) -> String {
+    lookup(key).0
+}
+
+/// 取配置的值, 为空时返回None
+pub(crate) fn config_value_opt(key: &str) -> Option<String> {
+    let value = config_value(key);
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// 配置文件中的表, 例如 [transcode_profiles]
+pub(crate) fn config_table(key: &str) -> Option<toml::value::Table> {
+    CONFIG
+        .get()
+        .and_then(|config| config.file.get(key))
+        .and_then(|value| value.as_table())
+        .cloned()
+}
+
+/// 同时下载的视频数
+pub(crate) fn concurrency() -> usize {
+    config_value("concurrency").parse().unwrap_or(1).max(1)
+}
+
+/// ffmpeg可执行文件
+pub(crate) fn ffmpeg_path() -> String {
+    config_value("ffmpeg_path")
+}
+
+/// ffmpeg没有进度时等待的时间, 为None时不限制
+pub(crate) fn ffmpeg_timeout() -> Option<std::time::Duration> {
+    match config_value("ffmpeg_timeout").parse().unwrap_or(0) {
+        0 => None,
+        seconds => Some(std::time::Duration::from_secs(seconds)),
+    }
+}
+
+/// 检查配置的值, 用于 --set 和 config set
+pub(crate) fn validate_config(key: &str, value: &str) -> Result<(), String> {
+    (config_item(key)?.validator)(value)
+}
+
+/// 名称对应的配置, 用于 config get 和 config set
+fn config_item(key: &str) -> Result<&'static ConfigItem, String> {
+    CONFIG_ITEMS
+        .iter()
+        .find(|item| item.key == key)
+        .ok_or_else(|| {
+            format!(
+                "未知的配置 : {}, 可以使用的配置 : {}",
+                key,
+                CONFIG_ITEMS.iter().map(|item| item.key).join(" / ")
+            )
+        })
+}
+
+fn validate_any(_: &str) -> Result<(), String> {
+    Ok(())
+}
+
+fn validate_proxy(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    match reqwest::Proxy::all(value) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("代理服务器格式错误 : {}", err)),
+    }
+}
+
+fn validate_url(value: &str) -> Result<(), String> {
+    if value.is_empty() || value.starts_with("http://") || value.starts_with("https://") {
+        Ok(())
+    } else {
+        Err("需要以 http:// 或 https:// 开头".to_string())
+    }
+}
+
+fn validate_ip_version(value: &str) -> Result<(), String> {
+    match value {
+        "auto" | "4" | "6" => Ok(()),
+        _ => Err("只能为 auto/4/6 其中之一".to_string()),
+    }
+}
+
+fn validate_muxer(value: &str) -> Result<(), String> {
+    match value {
+        "auto" | "ffmpeg" | "libav" | "native" => Ok(()),
+        _ => Err("只能为 auto/ffmpeg/libav/native 其中之一".to_string()),
+    }
+}
+
+fn validate_chapters(value: &str) -> Result<(), String> {
+    match value {
+        "embed" | "file" | "both" | "none" => Ok(()),
+        _ => Err("只能为 embed/file/both/none 其中之一".to_string()),
+    }
+}
+
+fn validate_format(value: &str) -> Result<(), String> {
+    match value {
+        "mp4" | "dash" | "choose" => Ok(()),
+        _ => Err("视频格式 只能为 mp4/dash/choose 其中之一".to_string()),
+    }
+}
+
+fn validate_quality(value: &str) -> Result<(), String> {
+    match value {
+        "choose" | "best" => Ok(()),
+        other => match other.parse::<i64>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("只能为 choose/best 或者数字".to_string()),
+        },
+    }
+}
+
+fn validate_number(value: &str) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(_) => Ok(()),
+        _ => Err("只能为数字".to_string()),
+    }
+}
+
+fn validate_positive_number(value: &str) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(()),
+        _ => Err("只能为大于0的数字".to_string()),
+    }
+}
+
+fn validate_filename_template(value: &str) -> Result<(), String> {
+    if value.contains("{title}") || value.contains("{bvid}") {
+        Ok(())
+    } else {
+        Err("文件名模板需要包含 {title} 或 {bvid}".to_string())
+    }
+}
+
+/// 配置
+pub(crate) async fn config() -> crate::Result<()> {
+    match app::nested_subcommand() {
+        Some(subcommand) => match subcommand.as_str() {
+            "get" => {
+                let key = app::config_key_value();
+                config_item(key.as_str()).map_err(anyhow::Error::msg)?;
+                println!("{}", config_value(key.as_str()));
+            }
+            "set" => {
+                let key = app::config_key_value();
+                let value = app::config_value_value();
+                validate_config(key.as_str(), value.as_str()).map_err(anyhow::Error::msg)?;
+                save_property(property_key(key.as_str()), value).await?;
+            }
+            "unset" => {
+                delete_property(property_key(app::config_key_value().as_str())).await?;
+            }
+            "list" => list(),
+            "edit" => edit()?,
+            "lock" => {
+                lock_properties(app::key_file_value()).await?;
+                println!("登录信息已加密");
+            }
+            "unlock" => {
+                unlock_properties().await?;
+                println!("登录信息已解密");
+            }
+            _ => app::print_help()?,
+        },
+        None => app::print_help()?,
+    }
+    Ok(())
+}
+
+fn list() {
+    println!("配置文件 : {}", config_file());
+    println!();
+    for item in CONFIG_ITEMS {
+        let (value, source) = lookup(item.key);
+        println!("{} = \"{}\" ({})", item.key, value, source.description());
+        println!("    {}", item.help);
+        println!("    环境变量 : {}", env_name(item.key));
+    }
+}
+
+/// 使用编辑器打开配置文件, 不存在时创建
+fn edit() -> crate::Result<()> {
+    let path = config_file();
+    if !Path::new(path.as_str()).exists() {
+        let mut template = String::new();
+        for item in CONFIG_ITEMS {
+            template.push_str(
+                format!("# {}\n# {} = \"{}\"\n\n", item.help, item.key, item.default).as_str(),
+            );
+        }
+        std::fs::write(path.as_str(), template)?;
+    }
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| {
+            if cfg!(target_os = "windows") {
+                "notepad".to_owned()
+            } else {
+                "vi".to_owned()
+            }
+        });
+    let status = Command::new(editor).arg(path.as_str()).status()?;
+    if !status.success() {
+        return Err(anyhow::Error::msg("编辑器未能成功运行"));
+    }
+    let content = std::fs::read_to_string(path.as_str())?;
+    let table = toml::from_str::<toml::value::Table>(content.as_str())
+        .map_err(|err| anyhow::Error::msg(format!("配置文件格式错误 : {}", err)))?;
+    for item in CONFIG_ITEMS {
+        if let Some(value) = table.get(item.key) {
+            (item.validator)(toml_to_string(value).as_str())
+                .map_err(|err| anyhow::Error::msg(format!("{} : {}", item.key, err)))?;
+        }
+    }
+    Ok(())
+}
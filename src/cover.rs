@@ -0,0 +1,233 @@
+use std::path::Path;
+
+use anyhow::Context;
+use itertools::Itertools;
+
+use crate::api::ViewPoint;
+use crate::down::print_line;
+use crate::ffmpeg::{run_ffmpeg, FfmpegMuxer};
+use crate::local::{allowed_file_name, join_paths};
+use crate::mux::Muxer;
+use crate::{app, down, http, mp4, web_api};
+
+/// 预览图中的列数和行数
+const SHEET_TILES: u32 = 4;
+
+/// 在下载之前检查生成缩略图需要的ffmpeg
+pub(crate) fn init_cover() -> crate::Result<()> {
+    if app::thumbnails_value().is_some() && !FfmpegMuxer.available() {
+        return Err(anyhow::Error::msg(
+            "生成缩略图需要ffmpeg, 请先安装ffmpeg, 或者检查 ffmpeg_path 配置",
+        ));
+    }
+    Ok(())
+}
+
+/// 下载图片到文件夹, stem为不含扩展名的文件名, 扩展名和url相同
+/// 文件已经存在时跳过, 返回保存的文件
+async fn save_image(url: &str, folder: &str, stem: &str) -> crate::Result<String> {
+    let url = url.replacen("http://", "https://", 1);
+    let extension = Path::new(url.split('?').next().unwrap())
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("jpg")
+        .to_owned();
+    let file = join_paths(vec![folder, format!("{}.{}", stem, extension).as_str()]);
+    if Path::new(&file).exists() {
+        return Ok(file);
+    }
+    let data = http::http_client()
+        .get(url.as_str())
+        .timeout(http::request_timeout())
+        .header("referer", "https://www.bilibili.com")
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    tokio::fs::write(file.as_str(), data).await?;
+    Ok(file)
+}
+
+/// 保存文件夹的封面, 失败时只打印错误, 不影响下载
+/// Jellyfin和Kodi会把文件夹中的poster/folder作为封面
+pub(crate) async fn save_folder_cover(url: &str, folders: &[&str], stem: &str) {
+    if url.is_empty() {
+        return;
+    }
+    for folder in folders {
+        match save_image(url, folder, stem).await {
+            Ok(file) => print_line(format!(" > 保存封面 : {}", file)),
+            Err(err) => print_line(format!(" > 未能保存封面 : {}", err)),
+        }
+    }
+}
+
+/// 合集的封面
+pub(crate) async fn collection_cover(mid: i64, sid: i64) -> Option<String> {
+    let result = match web_api().await {
+        Ok(web_api) => web_api.collection_archives(mid, sid, 1, 1).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(archives) => Some(archives.meta.cover),
+        Err(err) => {
+            print_line(format!("未能读取合集的封面 : {}", err));
+            None
+        }
+    }
+}
+
+/// 收藏夹的封面
+pub(crate) async fn fav_folder_cover(fid: i64) -> Option<String> {
+    let result = match web_api().await {
+        Ok(web_api) => web_api.fav_folder_info(fid).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(info) => Some(info.cover),
+        Err(err) => {
+            print_line(format!("未能读取收藏夹的封面 : {}", err));
+            None
+        }
+    }
+}
+
+/// 下载完一个视频之后, 按照 --cover 保存封面, 按照 --thumbnails 生成缩略图
+/// view_points是下载时读取的章节, 读取失败时为None, 失败时只打印错误, 不影响下载
+pub(crate) async fn after_download(bvid: &str, view_points: Option<&[ViewPoint]>, file: &str) {
+    let cover = app::cover_value();
+    let thumbnails = app::thumbnails_value();
+    if !cover && thumbnails.is_none() {
+        return;
+    }
+    let folder = Path::new(file)
+        .parent()
+        .and_then(|parent| parent.to_str())
+        .unwrap_or(".");
+    let stem = Path::new(file)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    if cover {
+        // 和视频同名的图片会被作为视频的封面
+        let result = match web_api().await {
+            Ok(web_api) => match web_api.archive_view(bvid).await {
+                Ok(view) => save_image(view.pic.as_str(), folder, stem).await,
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(file) => print_line(format!(" > 保存封面 : {}", file)),
+            Err(err) => print_line(format!(" > 未能保存封面 : {}", err)),
+        }
+    }
+    if let Some(mode) = thumbnails {
+        let view_points = match mode.as_str() {
+            "chapters" => match view_points {
+                Some(view_points) => Some(view_points.to_vec()),
+                None => {
+                    print_line(" > 未能生成缩略图 : 没有读取到章节");
+                    return;
+                }
+            },
+            _ => None,
+        };
+        // ffmpeg在阻塞线程中运行, 不阻塞同时下载的其他视频
+        let (file, folder, stem) = (file.to_owned(), folder.to_owned(), stem.to_owned());
+        let result = down::run_blocking(move || match view_points {
+            Some(view_points) => chapter_thumbnails(&view_points, &file, &folder, &stem),
+            None => contact_sheet(&file, &folder, &stem),
+        })
+        .await;
+        if let Err(err) = result {
+            print_line(format!(" > 未能生成缩略图 : {}", err));
+        }
+    }
+}
+
+/// 在视频中平均选取画面, 拼接为一张预览图
+fn contact_sheet(file: &str, folder: &str, stem: &str) -> crate::Result<()> {
+    let output = join_paths(vec![folder, format!("{}.sheet.jpg", stem).as_str()]);
+    if Path::new(&output).exists() {
+        return Ok(());
+    }
+    let duration = mp4::duration(file).with_context(|| "未能读取视频的时长")?;
+    if duration <= 0.0 {
+        return Err(anyhow::Error::msg("视频的时长为0"));
+    }
+    let filter = format!(
+        "fps={:.6},scale=320:-2,tile={}x{}",
+        (SHEET_TILES * SHEET_TILES) as f64 / duration,
+        SHEET_TILES,
+        SHEET_TILES
+    );
+    run_ffmpeg(
+        &[
+            "-y",
+            "-i",
+            file,
+            "-vf",
+            filter.as_str(),
+            "-frames:v",
+            "1",
+            "-q:v",
+            "3",
+            output.as_str(),
+        ],
+        "生成预览图",
+    )?;
+    print_line(format!(" > 生成预览图 : {}", output));
+    Ok(())
+}
+
+/// 在每个章节的开始截取一张图片, 保存到 <视频名称>.chapters 文件夹
+/// 没有章节时生成预览图
+fn chapter_thumbnails(
+    view_points: &[ViewPoint],
+    file: &str,
+    folder: &str,
+    stem: &str,
+) -> crate::Result<()> {
+    if view_points.is_empty() {
+        print_line(" > 没有章节, 生成预览图");
+        return contact_sheet(file, folder, stem);
+    }
+    let chapters_dir = join_paths(vec![folder, format!("{}.chapters", stem).as_str()]);
+    std::fs::create_dir_all(chapters_dir.as_str())?;
+    for (index, point) in view_points.iter().enumerate() {
+        let output = join_paths(vec![
+            chapters_dir.as_str(),
+            allowed_file_name(&format!("{:02} {}.jpg", index + 1, point.content)).as_str(),
+        ]);
+        if Path::new(&output).exists() {
+            continue;
+        }
+        let position = point.from.to_string();
+        run_ffmpeg(
+            &[
+                "-y",
+                "-ss",
+                position.as_str(),
+                "-i",
+                file,
+                "-frames:v",
+                "1",
+                "-q:v",
+                "3",
+                output.as_str(),
+            ],
+            format!("章节缩略图 ({}/{})", index + 1, view_points.len()).as_str(),
+        )?;
+    }
+    print_line(format!(
+        " > 生成章节缩略图 : {} ({})",
+        chapters_dir,
+        view_points
+            .iter()
+            .map(|point| point.content.as_str())
+            .join(" / ")
+    ));
+    Ok(())
+}
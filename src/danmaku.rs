@@ -0,0 +1,242 @@
+use lazy_static::lazy_static;
+
+use crate::api::strip_html;
+
+lazy_static! {
+    static ref DANMAKU_PATTERN: regex::Regex =
+        regex::Regex::new(r#"<d p="([^"]*)">([^<]*)</d>"#).unwrap();
+}
+
+/// 画面的宽度和高度, 和播放器的分辨率无关
+const PLAY_RES_X: f64 = 1920.0;
+const PLAY_RES_Y: f64 = 1080.0;
+
+/// 默认字号(25)对应的字体大小
+const FONT_SIZE: f64 = 50.0;
+
+/// 滚动弹幕经过画面的时间(秒)
+const SCROLL_DURATION: f64 = 8.0;
+
+/// 顶部和底部弹幕停留的时间(秒)
+const FIXED_DURATION: f64 = 4.0;
+
+/// 滚动弹幕只使用画面上方的区域, 避免遮挡字幕
+const SCROLL_AREA: f64 = 0.8;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Scroll,
+    Top,
+    Bottom,
+}
+
+/// 一条弹幕
+struct Danmaku {
+    /// 出现的时间(秒)
+    time: f64,
+    mode: Mode,
+    size: f64,
+    /// RGB颜色
+    color: u32,
+    text: String,
+}
+
+impl Danmaku {
+    /// 估算弹幕的宽度, 半角字符按照一半的宽度计算
+    fn width(&self) -> f64 {
+        self.text
+            .chars()
+            .map(|char| if char.is_ascii() { 0.5 } else { 1.0 })
+            .sum::<f64>()
+            * self.size
+    }
+}
+
+/// 解析弹幕的xml, 属性p为 时间,类型,字号,颜色,...
+/// 高级弹幕(7)和代码弹幕(8)不能转换, 会被忽略
+fn parse_danmaku(xml: &str) -> Vec<Danmaku> {
+    let mut list = vec![];
+    for captures in DANMAKU_PATTERN.captures_iter(xml) {
+        let p = captures
+            .get(1)
+            .unwrap()
+            .as_str()
+            .split(',')
+            .collect::<Vec<&str>>();
+        if p.len() < 4 {
+            continue;
+        }
+        let mode = match p[1] {
+            "1" | "2" | "3" => Mode::Scroll,
+            "4" => Mode::Bottom,
+            "5" => Mode::Top,
+            _ => continue,
+        };
+        let text = strip_html(captures.get(2).unwrap().as_str())
+            .replace(['\r', '\n'], " ")
+            .trim()
+            .to_owned();
+        if text.is_empty() {
+            continue;
+        }
+        list.push(Danmaku {
+            time: p[0].parse().unwrap_or(0.0),
+            mode,
+            size: p[2].parse::<f64>().unwrap_or(25.0) / 25.0 * FONT_SIZE,
+            color: p[3].parse().unwrap_or(0xFFFFFF),
+            text,
+        });
+    }
+    list.sort_by(|a, b| a.time.total_cmp(&b.time));
+    list
+}
+
+/// 将弹幕的xml转换为ASS字幕
+pub(crate) fn danmaku_ass(xml: &str) -> String {
+    let list = parse_danmaku(xml);
+    let lane_height = FONT_SIZE + 4.0;
+    let scroll_lanes = (PLAY_RES_Y * SCROLL_AREA / lane_height) as usize;
+    let fixed_lanes = (PLAY_RES_Y / 2.0 / lane_height) as usize;
+    // 滚动弹幕每一行最后一条弹幕的 (时间, 宽度)
+    let mut scroll_last: Vec<Option<(f64, f64)>> = vec![None; scroll_lanes];
+    // 顶部和底部弹幕每一行空出来的时间
+    let mut top_free = vec![0.0; fixed_lanes];
+    let mut bottom_free = vec![0.0; fixed_lanes];
+    let mut content = ass_header();
+    for danmaku in &list {
+        let width = danmaku.width();
+        let (start, end, effect) = match danmaku.mode {
+            Mode::Scroll => {
+                let free = scroll_last
+                    .iter()
+                    .map(|last| match last {
+                        Some(last) => scroll_free_time(*last, width),
+                        None => 0.0,
+                    })
+                    .collect::<Vec<f64>>();
+                let lane = choose_lane(&free, danmaku.time);
+                scroll_last[lane] = Some((danmaku.time, width));
+                let y = lane as f64 * lane_height;
+                (
+                    danmaku.time,
+                    danmaku.time + SCROLL_DURATION,
+                    format!("\\move({:.0},{:.0},{:.0},{:.0})", PLAY_RES_X, y, -width, y),
+                )
+            }
+            Mode::Top => {
+                let lane = choose_lane(&top_free, danmaku.time);
+                top_free[lane] = danmaku.time + FIXED_DURATION;
+                (
+                    danmaku.time,
+                    danmaku.time + FIXED_DURATION,
+                    format!(
+                        "\\an8\\pos({:.0},{:.0})",
+                        PLAY_RES_X / 2.0,
+                        lane as f64 * lane_height
+                    ),
+                )
+            }
+            Mode::Bottom => {
+                let lane = choose_lane(&bottom_free, danmaku.time);
+                bottom_free[lane] = danmaku.time + FIXED_DURATION;
+                (
+                    danmaku.time,
+                    danmaku.time + FIXED_DURATION,
+                    format!(
+                        "\\an2\\pos({:.0},{:.0})",
+                        PLAY_RES_X / 2.0,
+                        PLAY_RES_Y - lane as f64 * lane_height
+                    ),
+                )
+            }
+        };
+        let mut tags = effect;
+        if danmaku.size != FONT_SIZE {
+            tags.push_str(format!("\\fs{:.0}", danmaku.size).as_str());
+        }
+        if danmaku.color != 0xFFFFFF {
+            tags.push_str(format!("\\c{}", ass_color(danmaku.color)).as_str());
+        }
+        content.push_str(
+            format!(
+                "Dialogue: 0,{},{},Danmaku,,0,0,0,,{{{}}}{}\n",
+                ass_time(start),
+                ass_time(end),
+                tags,
+                escape_ass(&danmaku.text)
+            )
+            .as_str(),
+        );
+    }
+    content
+}
+
+/// 上一条滚动弹幕完全进入画面, 并且新的弹幕在它离开画面之前追不上的时候, 这一行才能放新的弹幕
+fn scroll_free_time(last: (f64, f64), width: f64) -> f64 {
+    let (time, last_width) = last;
+    let last_speed = (PLAY_RES_X + last_width) / SCROLL_DURATION;
+    let speed = (PLAY_RES_X + width) / SCROLL_DURATION;
+    let entered = time + last_width / last_speed;
+    let not_catch_up = time + SCROLL_DURATION - PLAY_RES_X / speed;
+    entered.max(not_catch_up)
+}
+
+/// 选择已经空出来的第一行, 都被占用时选择最早空出来的一行
+fn choose_lane(free: &[f64], time: f64) -> usize {
+    match free.iter().position(|free| *free <= time) {
+        Some(lane) => lane,
+        None => free
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(lane, _)| lane)
+            .unwrap_or(0),
+    }
+}
+
+fn ass_header() -> String {
+    format!(
+        "[Script Info]\n\
+         ScriptType: v4.00+\n\
+         PlayResX: {:.0}\n\
+         PlayResY: {:.0}\n\
+         WrapStyle: 2\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Danmaku,sans-serif,{:.0},&H33FFFFFF,&H33FFFFFF,&H33000000,&H33000000,1,0,0,0,100,100,0,0,1,1.5,0,7,0,0,0,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        PLAY_RES_X, PLAY_RES_Y, FONT_SIZE
+    )
+}
+
+/// ASS的时间 时:分:秒.百分之一秒
+fn ass_time(seconds: f64) -> String {
+    let centis = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// ASS的颜色顺序为 BGR
+fn ass_color(rgb: u32) -> String {
+    format!(
+        "&H{:02X}{:02X}{:02X}&",
+        rgb & 0xFF,
+        (rgb >> 8) & 0xFF,
+        (rgb >> 16) & 0xFF
+    )
+}
+
+/// 花括号会被当作样式, 反斜杠会被当作转义
+fn escape_ass(text: &str) -> String {
+    text.replace('\\', "＼")
+        .replace('{', "｛")
+        .replace('}', "｝")
+}
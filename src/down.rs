@@ -1,61 +1,94 @@
 use std::env::current_dir;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
-use bilirust::{
-    web::{Season, SsState},
-    Audio, FavListOrder, Video, FNVAL_DASH, VIDEO_QUALITY_4K,
-};
+use bilirust::{FNVAL_DASH, VIDEO_QUALITY_4K};
 use dialoguer::Select;
-use futures::stream::TryStreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use futures::stream::{StreamExt, TryStreamExt};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::io::StreamReader;
 
+use crate::api::{PgcDashMedia, PgcEpisode, PgcSeason, PgcSeasonItem, PgcSection, WebApi};
 use crate::local::{allowed_file_name, join_paths};
-use crate::{app, ffmpeg, login_client};
+use crate::region::{self, Region};
+use crate::{app, chapter, config, cover, http, login_client, sync, transcode};
 
 lazy_static! {
     static ref SHORT_PATTERN: regex::Regex =
         regex::Regex::new(r"//b\d+\.tv/([0-9a-zA-Z]+)$").unwrap();
-    static ref BV_PATTERN: regex::Regex = regex::Regex::new(r"BV[0-9a-zA-Z]{10}").unwrap();
-    static ref SERIES_PATTERN: regex::Regex = regex::Regex::new(r"((ep)|(ss))[0-9]+").unwrap();
-    static ref USER_COLLECTION_DETAIL_PATTERN: regex::Regex =
+    pub(crate) static ref BV_PATTERN: regex::Regex =
+        regex::Regex::new(r"BV[0-9a-zA-Z]{10}").unwrap();
+    pub(crate) static ref SERIES_PATTERN: regex::Regex =
+        regex::Regex::new(r"((ep)|(ss))[0-9]+").unwrap();
+    pub(crate) static ref USER_COLLECTION_DETAIL_PATTERN: regex::Regex =
         regex::Regex::new(r"/([0-9]+)/channel/collectiondetail\?sid=([0-9]+)").unwrap();
-    static ref USER_FAV_LIST_PATTERN: regex::Regex =
+    pub(crate) static ref USER_FAV_LIST_PATTERN: regex::Regex =
         regex::Regex::new(r"/favlist\?fid=([0-9]+)").unwrap();
+    pub(crate) static ref USER_SPACE_PATTERN: regex::Regex =
+        regex::Regex::new(r"space\.bilibili\.com/([0-9]+)").unwrap();
+    pub(crate) static ref MULTI_PROGRESS: MultiProgress = MultiProgress::new();
+    /// 终端界面中下载时, 下载的输出和进度发送到界面, 不打印到终端
+    static ref PROGRESS: Mutex<Option<UnboundedSender<String>>> = Mutex::new(None);
 }
 
-// 新下载
-pub(crate) async fn down() -> crate::Result<()> {
-    let mut url = app::url_value();
-    let ss = app::parse_input_url_value();
-    if let Some(_) = SHORT_PATTERN.find(url.as_str()) {
-        url = url.replace("http://", "https://");
-        let rsp = reqwest::ClientBuilder::new()
-            .redirect(reqwest::redirect::Policy::none())
-            .build()?
-            .get(&url)
-            .send()
-            .await?;
-        match rsp.status().as_u16() {
-            302 => {
-                let headers = rsp.headers();
-                let location = headers.get("location");
-                if let Some(location) = location {
-                    url = location.to_str()?.to_owned();
-                }
+/// 终端界面中下载时进度更新的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 设置接收下载输出的channel, 设置后不显示进度条
+pub(crate) fn set_progress(sender: UnboundedSender<String>) {
+    MULTI_PROGRESS.set_draw_target(ProgressDrawTarget::hidden());
+    *PROGRESS.lock().unwrap() = Some(sender);
+}
+
+/// 是否在终端界面中下载, 这时不能在终端中交互
+pub(crate) fn in_tui() -> bool {
+    PROGRESS.lock().unwrap().is_some()
+}
+
+/// 打印下载的输出, 终端界面中下载时发送到界面
+pub(crate) fn print_line(line: impl Into<String>) {
+    let line = line.into();
+    match PROGRESS.lock().unwrap().as_ref() {
+        Some(sender) => {
+            if !line.trim().is_empty() {
+                let _ = sender.send(line.trim().to_owned());
             }
-            _ => return Err(anyhow::Error::msg("resolve short links error")),
         }
+        None => println!("{}", line),
+    }
+}
+
+/// 终端界面中下载时发送下载的进度
+fn send_progress(title: &str, position: u64, size: u64) {
+    if let Some(sender) = PROGRESS.lock().unwrap().as_ref() {
+        let _ = sender.send(format!(
+            "{} {} / {}",
+            title,
+            HumanBytes(position),
+            HumanBytes(size)
+        ));
     }
+}
+
+// 新下载
+pub(crate) async fn down() -> crate::Result<()> {
+    let url = resolve_short_link(app::url_value()).await?;
+    down_url(url).await
+}
+
+/// 按照网址的类型下载视频, 番剧, 合集或者收藏夹
+pub(crate) async fn down_url(url: String) -> crate::Result<()> {
     if let Some(find) = BV_PATTERN.find(url.as_str()) {
         return down_bv((&(url[find.start()..find.end()])).to_owned()).await;
     }
     if let Some(find) = SERIES_PATTERN.find(url.as_str()) {
-        return down_series((&(url[find.start()..find.end()])).to_owned(), url, ss).await;
+        return down_series((&(url[find.start()..find.end()])).to_owned()).await;
     }
     if let Some(find) = USER_COLLECTION_DETAIL_PATTERN.captures(url.as_str()) {
         let mid: i64 = find.get(1).unwrap().as_str().parse().unwrap();
@@ -69,157 +102,157 @@ pub(crate) async fn down() -> crate::Result<()> {
     Ok(())
 }
 
-async fn down_bv(bv: String) -> crate::Result<()> {
+/// 解析手机分享的短链接, 不是短链接时原样返回
+pub(crate) async fn resolve_short_link(mut url: String) -> crate::Result<String> {
+    if let Some(_) = SHORT_PATTERN.find(url.as_str()) {
+        url = url.replace("http://", "https://");
+        let rsp = http::no_redirect_client()
+            .get(&url)
+            .timeout(http::read_timeout())
+            .send()
+            .await?;
+        match rsp.status().as_u16() {
+            302 => {
+                let headers = rsp.headers();
+                let location = headers.get("location");
+                if let Some(location) = location {
+                    url = location.to_str()?.to_owned();
+                }
+            }
+            _ => return Err(anyhow::Error::msg("resolve short links error")),
+        }
+    }
+    Ok(url)
+}
+
+pub(crate) async fn down_bv(bv: String) -> crate::Result<()> {
     let client = login_client().await?;
     // 获取基本信息
-    println!();
-    println!("匹配到 : {}", bv.clone());
-    let info = client.bv_info(bv.clone()).await.unwrap();
-    println!("  {}", &info.title);
+    print_line("");
+    print_line(format!("匹配到 : {}", bv.clone()));
+    let info = client.archive_view(&bv).await?;
+    print_line(format!("  {}", &info.title));
     // 获取格式+获取清晰度
     let format_str = app::format_value();
     let format = app::format_fnval(format_str);
     let vu = client
-        .bv_download_url(bv.clone(), info.cid, format, VIDEO_QUALITY_4K)
-        .await
-        .unwrap();
+        .play_url(&bv, info.cid, format, VIDEO_QUALITY_4K)
+        .await?;
     match format_str {
         "dash" => {
             // 选择清晰度
-            if vu.support_formats.len() == 0 {
-                panic!("未找到");
-            }
-            let mut choose_string: Vec<String> = vec![];
-            let mut choose_int: Vec<i64> = vec![];
-            for i in 0..vu.support_formats.len() {
-                let f = &vu.support_formats[i];
-                choose_string.push(f.new_description.clone());
-                choose_int.push(f.quality);
+            if vu.support_formats.is_empty() {
+                return Err(anyhow::Error::msg("未找到可以下载的清晰度"));
             }
-            let choose = Select::new()
-                .with_prompt("选择视频质量")
-                .default(0)
-                .items(&choose_string)
-                .interact()
-                .unwrap();
-            let quality_video = choose_int[choose];
+            let video_ids = vu.dash.video.iter().map(|x| x.id).collect_vec();
+            let formats = vu
+                .support_formats
+                .iter()
+                .map(|f| (f.new_description.clone(), f.quality))
+                .collect_vec();
+            let quality_video = choose_video_quality(&video_ids, formats);
             // 音频
-            let mut choose_string: Vec<String> = vec![];
-            let mut choose_int: Vec<i64> = vec![];
-            for i in 0..vu.dash.audio.len() {
-                let f = &vu.dash.audio[i];
-                match f.id {
-                    30216 => {
-                        choose_string.push("64K".to_owned());
-                        choose_int.push(f.id);
-                    }
-                    30232 => {
-                        choose_string.push("132K".to_owned());
-                        choose_int.push(f.id);
-                    }
-                    30280 => {
-                        choose_string.push("192K".to_owned());
-                        choose_int.push(f.id);
-                    }
-                    _ => {
-                        choose_string.push(format!("AUDIO-{}", f.id));
-                        choose_int.push(f.id);
-                    }
-                }
-            }
-            let choose = Select::new()
-                .with_prompt("选择音频质量")
-                .default(0)
-                .items(&choose_string)
-                .interact()
-                .unwrap();
-            let quality_audio = choose_int[choose];
+            let audio_ids = vu.dash.audio.iter().map(|x| x.id).collect_vec();
+            let quality_audio = choose_audio_quality(&audio_ids);
             // 下载
-            let mut video: Option<Video> = None;
+            let mut video: Option<PgcDashMedia> = None;
             for x in vu.dash.video {
                 if x.id == quality_video {
                     video = Some(x);
                     break;
                 }
             }
-            let mut audio: Option<Audio> = None;
+            let mut audio: Option<PgcDashMedia> = None;
             for x in vu.dash.audio {
                 if x.id == quality_audio {
                     audio = Some(x);
                     break;
                 }
             }
-            let video = video.unwrap();
-            let audio = audio.unwrap();
+            let video = video.with_context(|| "未找到视频")?;
+            let audio = audio.with_context(|| "未找到音频")?;
             // 文件名
-            let name = allowed_file_name(&info.title);
-            let audio_file = format!("{}.audio", name);
-            let video_file = format!("{}.video", name);
-            let mix_file = format!("{}.mp4", name);
-            println!("下载到文件 : {}", &mix_file);
+            let folder = output_dir();
+            let name = file_name(&info.title, &bv);
+            let audio_file = join_paths(vec![folder.as_str(), &format!("{}.audio", name)]);
+            let video_file = join_paths(vec![folder.as_str(), &format!("{}.video", name)]);
+            let mix_file = join_paths(vec![folder.as_str(), &format!("{}.mp4", name)]);
+            print_line(format!("下载到文件 : {}", &mix_file));
             if Path::new(&mix_file).exists() {
-                panic!("文件已存在");
+                return Err(anyhow::Error::msg(format!("文件已存在 : {}", mix_file)));
             }
             // 下载
-            down_file_to(&audio.base_url, &audio_file, "下载音频").await;
-            println!(" > 下载音频");
-            down_file_to(&video.base_url, &video_file, "下载视频").await;
-            println!(" > 下载视频");
-            println!(" > 合并视频");
-            let mix_result = ffmpeg::ffmpeg_merge_file(vec![&video_file, &audio_file], &mix_file);
-            mix_result.unwrap();
-            println!(" > 清理合并前的数据");
+            down_file_to(&audio.base_url, &audio_file, "下载音频").await?;
+            print_line(" > 下载音频");
+            down_file_to(&video.base_url, &video_file, "下载视频").await?;
+            print_line(" > 下载视频");
+            let view_points =
+                chapter::fetch_view_points(&bv, info.cid, chapter::need_chapters()).await;
+            let chapters = chapter::chapters(view_points.as_deref());
+            print_line(" > 合并视频");
+            merge_file_blocking(&video_file, &audio_file, &chapters, &mix_file).await?;
+            chapter::save_chapters_file(&chapters, &mix_file);
+            print_line(" > 清理合并前的数据");
             let _ = std::fs::remove_file(&audio_file);
             let _ = std::fs::remove_file(&video_file);
+            cover::after_download(&bv, view_points.as_deref(), &mix_file).await;
         }
         "mp4" => {
-            let file = format!("{}.mp4", info.title);
-            println!("下载到文件 : {}", &file);
+            let file = join_paths(vec![
+                output_dir().as_str(),
+                &format!("{}.mp4", file_name(&info.title, &bv)),
+            ]);
+            print_line(format!("下载到文件 : {}", &file));
             if Path::new(&file).exists() {
-                panic!("文件夹已存在");
+                return Err(anyhow::Error::msg(format!("文件已存在 : {}", file)));
             }
-            down_file_to(&(vu.durl.first().unwrap().url), &file, "下载中").await;
-            println!("下载完成");
+            let durl = vu.durl.first().with_context(|| "未找到视频")?;
+            down_file_to(&durl.url, &file, "下载中").await?;
+            let transcode_file = file.clone();
+            run_blocking(move || transcode::transcode_in_place(&transcode_file)).await?;
+            // 不经过合并, 章节只能保存为单独的文件, 只保存到视频中时不需要读取章节
+            let view_points =
+                chapter::fetch_view_points(&bv, info.cid, chapter::save_chapters_txt()).await;
+            chapter::save_chapters_file(&chapter::chapters(view_points.as_deref()), &file);
+            cover::after_download(&bv, view_points.as_deref(), &file).await;
+            print_line("下载完成");
         }
-        &_ => panic!("e2"),
+        &_ => return Err(anyhow::Error::msg(format!("不支持的格式 : {}", format_str))),
     };
     Ok(())
 }
 
-/// 下载一系列视频
-async fn down_series(id: String, url: String, ss: bool) -> crate::Result<()> {
+/// 下载一系列视频, id为ss或ep开头的id
+pub(crate) async fn down_series(id: String) -> crate::Result<()> {
     let client = login_client().await?;
-    println!();
-    println!("匹配到合集 : {}", id);
-    let ss_state = if ss {
-        client.videos_info_by_url(url).await.unwrap()
-    } else {
-        client.videos_info(id.clone()).await.unwrap()
-    };
-    println!("  系列名称 : {}", ss_state.media_info.series.clone());
-    println!(
+    print_line("");
+    print_line(format!("匹配到合集 : {}", id));
+    // 还不知道番剧的地区, 按照当前网络所在的地区说明地区限制
+    let series = client
+        .pgc_season(config::config_value("api_endpoint").as_str(), id.as_str())
+        .await
+        .map_err(|err| region::region_error(Region::Mainland, err))?;
+    let season_list = series.season_list();
+    print_line(format!("  系列名称 : {}", series.series_title()));
+    print_line(format!(
         "  包含番剧 : {} ",
-        (&ss_state.season_list)
+        season_list
             .iter()
             .map(|i| i.season_title.as_str())
             .join(" / ")
-    );
-    let project_dir = join_paths(vec![
-        current_dir().unwrap().to_str().unwrap(),
-        allowed_file_name(ss_state.media_info.series.as_str()).as_str(),
-    ]);
-    println!("  保存位置 : {}", project_dir.as_str());
+    ));
+    let project_dir = series_dir(&series);
+    print_line(format!("  保存位置 : {}", project_dir.as_str()));
     // todo
     if Path::new(project_dir.as_str()).exists() {
         //panic!("文件夹已存在, 请使用continue");
     }
-    std::fs::create_dir_all(project_dir.as_str()).unwrap();
+    std::fs::create_dir_all(project_dir.as_str())?;
 
     //
     let fetch_ids = if app::choose_seasons_value() {
-        println!();
-        let titles = (&ss_state)
-            .season_list
+        print_line("");
+        let titles = season_list
             .iter()
             .map(|x| format!("{} ({})", x.season_id, x.season_title.as_str(),))
             .collect_vec();
@@ -228,256 +261,670 @@ async fn down_series(id: String, url: String, ss: bool) -> crate::Result<()> {
             .with_prompt("请选择要下载的合集")
             .items(&titles)
             .defaults(&default_selects)
-            .interact()
-            .unwrap();
+            .interact()?;
         let mut id_list: Vec<i64> = vec![];
         for i in 0..titles.len() {
             if selects.contains(&i) {
-                id_list.push(ss_state.season_list[i].season_id);
+                id_list.push(season_list[i].season_id);
             }
         }
         id_list
     } else {
-        (&ss_state)
-            .season_list
-            .iter()
-            .map(|x| x.season_id)
-            .collect_vec()
+        season_list.iter().map(|x| x.season_id).collect_vec()
     };
 
     // 找到所有的ss
     // 找到所有ss的bv
-    println!();
-    println!("搜索视频");
-    let mut sss: Vec<(Season, SsState, String)> = vec![];
-    for x in ss_state.season_list {
+    print_line("");
+    print_line("搜索视频");
+    let episodes = app::episodes_value();
+    let latest = app::latest_value();
+    let extras = app::extras_value();
+    let mut sss: Vec<SeasonDownload> = vec![];
+    for x in season_list {
         if !fetch_ids.contains(&x.season_id) {
             continue;
         }
-        let videos_info = client
-            .videos_info(format!("ss{}", x.season_id))
+        let (mut season, region) = region::fetch_season(&client, x.season_id)
             .await
-            .unwrap();
-        let x_dir_name = format!(
-            "{} ({}) {}",
-            x.season_id,
-            x.season_title.as_str(),
-            videos_info.media_info.season_title.as_str(),
-        );
-        println!(
-            "  {} : 共 {} 个视频",
-            x_dir_name.as_str(),
-            videos_info.init_ep_list.len()
-        );
-        sss.push((x, videos_info, x_dir_name));
+            .map_err(|err| region::region_error(Region::Mainland, err))?;
+        let x_dir_name = season_dir_name(&x, &season);
+        let total = season.episodes.len();
+        let indexes = select_indexes(total, episodes.clone(), latest, false);
+        if indexes.len() < total {
+            print_line(format!(
+                "  {} : 共 {} 个视频, 选择了 {} 个",
+                x_dir_name.as_str(),
+                total,
+                indexes.len()
+            ));
+        } else {
+            print_line(format!("  {} : 共 {} 个视频", x_dir_name.as_str(), total));
+        }
+        if region != Region::Mainland {
+            match region.endpoint() {
+                Some(endpoint) => {
+                    print_line(format!("    仅限{}地区, 使用 {}", region.name(), endpoint))
+                }
+                None => print_line(format!(
+                    "    仅限{}地区, 没有配置 {}, 将使用默认的接口服务器",
+                    region.name(),
+                    region.config_key()
+                )),
+            }
+        }
+        let season_extras = if extras.is_some() {
+            let season_extras = season_extras(std::mem::take(&mut season.section));
+            if !season_extras.is_empty() {
+                print_line(format!(
+                    "    正片以外的内容 : {} 个 ({})",
+                    season_extras.len(),
+                    season_extras
+                        .iter()
+                        .map(|extra| extra.section.as_str())
+                        .unique()
+                        .join(" / ")
+                ));
+            }
+            season_extras
+        } else {
+            vec![]
+        };
+        sss.push((x, season, x_dir_name, region, indexes, season_extras));
+    }
+    if app::choose_episodes_value() {
+        for x in sss.iter_mut() {
+            print_line("");
+            x.4 = choose_episodes(x.2.as_str(), &x.1, &x.4);
+        }
+    }
+    if extras.as_deref() == Some("choose") {
+        for x in sss.iter_mut().filter(|x| !x.5.is_empty()) {
+            print_line("");
+            x.5 = choose_extras(x.2.as_str(), std::mem::take(&mut x.5));
+        }
     }
-    println!();
-    println!("下载视频");
-    for x in &sss {
+    print_line("");
+    print_line("下载视频");
+    // 特别篇在所有季中连续编号
+    let mut special_number = 0;
+    for (index, x) in sss.iter().enumerate() {
         let ss_dir = join_paths(vec![project_dir.as_str(), x.2.as_str()]);
-        std::fs::create_dir_all(ss_dir.as_str()).unwrap();
-        let mut i = 0;
-        for ep in &x.1.init_ep_list {
-            let name = format!(
-                "{}. ({}) {}",
-                {
-                    let tmp = i;
-                    i += 1;
-                    tmp
-                },
-                ep.title_format,
-                ep.long_title
-            );
-            let name = allowed_file_name(&name);
-            println!();
-            println!("{}", &name);
-            let audio_name = format!("{}.audio", name);
-            let video_name = format!("{}.video", name);
-            let final_name = format!("{}.mp4", name);
-            let audio_file = join_paths(vec![&ss_dir, &audio_name]);
-            let video_file = join_paths(vec![&ss_dir, &video_name]);
-            let final_file = join_paths(vec![&ss_dir, &final_name]);
-            if Path::new(&&final_file).exists() {
+        std::fs::create_dir_all(ss_dir.as_str())?;
+        if app::cover_value() {
+            // 第一季的海报同时作为系列的海报
+            let folders = if index == 0 {
+                vec![project_dir.as_str(), ss_dir.as_str()]
+            } else {
+                vec![ss_dir.as_str()]
+            };
+            cover::save_folder_cover(x.1.cover.as_str(), &folders, "poster").await;
+        }
+        for (i, ep) in x.1.episodes.iter().enumerate() {
+            if !x.4.contains(&i) {
                 continue;
             }
-            let bv = client
-                .bv_download_url(
-                    if !ep.bvid.is_empty() {
-                        ep.bvid.clone()
-                    } else {
-                        bilirust::av_to_bv(ep.aid)
-                    },
-                    ep.cid.clone(),
-                    FNVAL_DASH,
-                    VIDEO_QUALITY_4K,
+            let name = episode_name(i, ep);
+            print_line("");
+            print_line(name.as_str());
+            down_episode(&client, x.3, ep.aid, &ep.bvid, ep.cid, &ss_dir, &name).await?;
+        }
+        for extra in &x.5 {
+            let ep = &extra.episode;
+            // 按照Jellyfin的规则: 预告放在系列的trailers中, 其他的作为系列的第0季
+            let (folder, name) = if extra.is_trailer() {
+                (
+                    join_paths(vec![project_dir.as_str(), TRAILERS_DIR]),
+                    format!("{} {} {}", extra.section, ep.title, ep.long_title),
                 )
-                .await
-                .unwrap();
-            let audio_url: &str = bv.dash.audio.first().unwrap().base_url.as_str();
-            let video_url = bv.dash.video.first().unwrap().base_url.as_str();
-            //
-            down_file_to(audio_url, &audio_file, "下载音频").await;
-            println!(" > 下载音频");
-            down_file_to(video_url, &video_file, "下载视频").await;
-            println!(" > 下载视频");
-            println!(" > 合并视频");
-            ffmpeg::ffmpeg_merge_file(vec![&video_file, &audio_file], &final_file).unwrap();
-            println!(" > 清理合并前的数据");
-            let _ = std::fs::remove_file(&audio_file);
-            let _ = std::fs::remove_file(&video_file);
+            } else {
+                special_number += 1;
+                (
+                    join_paths(vec![project_dir.as_str(), SPECIALS_DIR]),
+                    format!(
+                        "S00E{:02} {} {} {}",
+                        special_number, extra.section, ep.title, ep.long_title
+                    ),
+                )
+            };
+            std::fs::create_dir_all(folder.as_str())?;
+            let name = allowed_file_name(name.trim());
+            print_line("");
+            print_line(name.as_str());
+            down_episode(&client, x.3, ep.aid, &ep.bvid, ep.cid, &folder, &name).await?;
         }
     }
-    println!();
-    println!("全部完成");
+    print_line("");
+    print_line("全部完成");
     Ok(())
 }
 
+/// 系列的文件夹, 番剧的每一季保存在其中
+pub(crate) fn series_dir(series: &PgcSeason) -> String {
+    join_paths(vec![
+        output_dir().as_str(),
+        allowed_file_name(series.series_title()).as_str(),
+    ])
+}
+
+/// 一季的文件夹名称, season_info为这一季的信息
+pub(crate) fn season_dir_name(season: &PgcSeasonItem, season_info: &PgcSeason) -> String {
+    format!(
+        "{} ({}) {}",
+        season.season_id,
+        season.season_title.as_str(),
+        season_info.season_title.as_str(),
+    )
+}
+
+/// 一集的文件名, index为在这一季中的位置
+pub(crate) fn episode_name(index: usize, ep: &PgcEpisode) -> String {
+    allowed_file_name(&format!(
+        "{}. ({}) {}",
+        index,
+        ep.title_format(),
+        ep.long_title
+    ))
+}
+
+/// 下载番剧的一集, 地区限制并且配置了地区的接口服务器时使用地区的接口服务器
+pub(crate) async fn down_episode(
+    client: &WebApi,
+    region: Region,
+    aid: i64,
+    bvid: &str,
+    cid: i64,
+    folder: &str,
+    name: &str,
+) -> crate::Result<()> {
+    let bvid = if !bvid.is_empty() {
+        bvid.to_owned()
+    } else {
+        bilirust::av_to_bv(aid)
+    };
+    let result = if region.use_endpoint() {
+        down_region_archive(client, region, aid, &bvid, cid, folder, name).await
+    } else {
+        down_dash_archive(client, bvid, Some(cid), folder, name).await
+    };
+    result.map_err(|err| region::region_error(region, err))
+}
+
+/// 要下载的一季: (季, 番剧的信息, 文件夹名称, 地区, 选择的剧集, 选择的正片以外的内容)
+type SeasonDownload = (
+    PgcSeasonItem,
+    PgcSeason,
+    String,
+    Region,
+    Vec<usize>,
+    Vec<Extra>,
+);
+
+/// 预告的文件夹
+const TRAILERS_DIR: &str = "trailers";
+
+/// SP / OVA 等特别篇的文件夹, 所有季的特别篇都作为第0季
+const SPECIALS_DIR: &str = "Season 00";
+
+/// 番剧中正片以外的内容
+struct Extra {
+    /// 所在的栏目, 例如 PV / SP
+    section: String,
+    episode: PgcEpisode,
+}
+
+impl Extra {
+    fn is_trailer(&self) -> bool {
+        let section = self.section.to_uppercase();
+        self.episode.is_preview() || section.contains("PV") || section.contains("预告")
+    }
+}
+
+/// 一季中正片以外的内容, 没有视频的条目会被忽略
+fn season_extras(sections: Vec<PgcSection>) -> Vec<Extra> {
+    sections
+        .into_iter()
+        .flat_map(|section| {
+            let title = section.title;
+            section
+                .episodes
+                .into_iter()
+                .filter(|ep| ep.cid > 0)
+                .map(move |episode| Extra {
+                    section: title.clone(),
+                    episode,
+                })
+        })
+        .collect()
+}
+
+/// 选择一季中要下载的正片以外的内容, 默认全部选中
+fn choose_extras(title: &str, extras: Vec<Extra>) -> Vec<Extra> {
+    let items = extras
+        .iter()
+        .map(|extra| {
+            format!(
+                "[{}] {} {}",
+                extra.section, extra.episode.title, extra.episode.long_title
+            )
+        })
+        .collect_vec();
+    let defaults = items.iter().map(|_| true).collect_vec();
+    let selects = dialoguer::MultiSelect::new()
+        .with_prompt(format!("请选择要下载的正片以外的内容 : {}", title))
+        .items(&items)
+        .defaults(&defaults)
+        .interact()
+        .unwrap();
+    extras
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| selects.contains(i))
+        .map(|(_, extra)| extra)
+        .collect()
+}
+
 async fn down_collection_detail(mid: i64, sid: i64) -> crate::Result<()> {
     let client = login_client().await?;
-    // 获取第一页
-    let mut current_page = 1;
-    let mut page = client
-        .collection_video_page(mid, sid, false, current_page, 20)
-        .await
-        .unwrap();
-    println!();
-    println!("获取到合集 : {}", page.meta.name);
-    println!();
-    let folder = allowed_file_name(page.meta.name.as_str());
+    let source = sync::fetch_collection_detail(&client, mid, sid).await?;
+    print_line("");
+    print_line(format!(
+        "获取到合集 : {} : 共 {} 个视频",
+        source.name,
+        source.archives.len()
+    ));
+    let cover_url = if app::cover_value() {
+        cover::collection_cover(mid, sid).await
+    } else {
+        None
+    };
+    // 合集从旧到新排列
+    down_source(&client, source, false, cover_url).await
+}
+
+async fn down_fav_list(fid: i64) -> crate::Result<()> {
+    let client = login_client().await?;
+    let source = sync::fetch_fav_list(&client, fid).await?;
+    print_line("");
+    print_line(format!(
+        "获取到收藏夹 : {} : 共 {} 个视频",
+        source.name,
+        source.archives.len()
+    ));
+    let cover_url = if app::cover_value() {
+        cover::fav_folder_cover(fid).await
+    } else {
+        None
+    };
+    // 收藏夹按照收藏时间从新到旧排列
+    down_source(&client, source, true, cover_url).await
+}
+
+/// 下载UP主投稿的全部视频
+pub(crate) async fn down_user_videos(mid: i64, name: String) -> crate::Result<()> {
+    let client = login_client().await?;
+    print_line("");
+    print_line(format!("获取到UP主 : {}", name));
+    let source = sync::fetch_user_videos(mid).await?;
+    print_line(format!("  共 {} 个视频", source.archives.len()));
+    // 投稿从新到旧排列
+    down_source(&client, source, true, None).await
+}
+
+/// 按照 --items 和 --latest 下载来源中的视频, cover_url为文件夹的封面
+async fn down_source(
+    client: &WebApi,
+    source: sync::SyncSource,
+    newest_first: bool,
+    cover_url: Option<String>,
+) -> crate::Result<()> {
+    let folder = join_paths(vec![
+        output_dir().as_str(),
+        allowed_file_name(source.name.as_str()).as_str(),
+    ]);
     std::fs::create_dir_all(folder.as_str()).unwrap();
-    loop {
-        // 下载视频
-        for archive in page.archives {
-            println!();
-            println!("{}", archive.title);
-            let name = allowed_file_name(&archive.title);
-            let audio_name = format!("{}.audio", name);
-            let video_name = format!("{}.video", name);
-            let final_name = format!("{}.mp4", name);
-            let audio_file = join_paths(vec![folder.as_str(), audio_name.as_str()]);
-            let video_file = join_paths(vec![folder.as_str(), video_name.as_str()]);
-            let final_file = join_paths(vec![folder.as_str(), final_name.as_str()]);
-            if Path::new(&&final_file).exists() {
-                continue;
-            }
-            //
-            let info = client.bv_info(archive.bvid).await.unwrap();
-            let video_url = client
-                .bv_download_url(info.bvid, info.cid, FNVAL_DASH, VIDEO_QUALITY_4K)
-                .await
-                .unwrap();
-            let audio_url = video_url.dash.audio.first().unwrap().base_url.as_str();
-            let video_url = video_url.dash.video.first().unwrap().base_url.as_str();
-            //
-            down_file_to(audio_url, &audio_file, "下载音频").await;
-            println!(" > 下载音频");
-            down_file_to(video_url, &video_file, "下载视频").await;
-            println!(" > 下载视频");
-            println!(" > 合并视频");
-            ffmpeg::ffmpeg_merge_file(vec![&video_file, &audio_file], &final_file).unwrap();
-            println!(" > 清理合并前的数据");
-            let _ = std::fs::remove_file(&audio_file);
-            let _ = std::fs::remove_file(&video_file);
+    if let Some(cover_url) = cover_url {
+        cover::save_folder_cover(cover_url.as_str(), &[folder.as_str()], "folder").await;
+    }
+    let total = source.archives.len();
+    let indexes = select_indexes(total, app::items_value(), app::latest_value(), newest_first);
+    if indexes.len() < total {
+        print_line(format!("  选择了 {} 个视频", indexes.len()));
+    }
+    let archives = source
+        .archives
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| indexes.contains(i))
+        .map(|(_, archive)| archive)
+        .collect_vec();
+    down_archives(client, &folder, archives).await?;
+    print_line("");
+    print_line("全部完成");
+    Ok(())
+}
+
+/// 按照范围(从1开始)和最新的数量选择要下载的序号(从0开始)
+/// newest_first为列表是否从新到旧排列
+fn select_indexes(
+    total: usize,
+    ranges: Option<Vec<(usize, usize)>>,
+    latest: Option<usize>,
+    newest_first: bool,
+) -> Vec<usize> {
+    let mut indexes = (0..total)
+        .filter(|i| match &ranges {
+            Some(ranges) => ranges
+                .iter()
+                .any(|(begin, end)| (*begin..=*end).contains(&(i + 1))),
+            None => true,
+        })
+        .collect_vec();
+    if let Some(latest) = latest {
+        if newest_first {
+            indexes.truncate(latest);
+        } else {
+            indexes.drain(..indexes.len().saturating_sub(latest));
         }
-        // 获取下一页
-        if page.page.page_size * page.page.page_num >= page.page.total {
-            break;
+    }
+    indexes
+}
+
+/// 选择一季中要下载的剧集, 默认选中 --episodes 和 --latest 选择的剧集
+fn choose_episodes(title: &str, season: &PgcSeason, selected: &[usize]) -> Vec<usize> {
+    let items = season
+        .episodes
+        .iter()
+        .enumerate()
+        .map(|(i, ep)| format!("{}. ({}) {}", i, ep.title_format(), ep.long_title))
+        .collect_vec();
+    let defaults = (0..items.len())
+        .map(|i| selected.contains(&i))
+        .collect_vec();
+    dialoguer::MultiSelect::new()
+        .with_prompt(format!("请选择要下载的剧集 : {}", title))
+        .items(&items)
+        .defaults(&defaults)
+        .interact()
+        .unwrap()
+}
+
+/// 下载到的文件夹: --output-dir > 配置中的output_dir > 当前文件夹
+pub(crate) fn output_dir() -> String {
+    let dir = match app::output_dir_value().or_else(|| config::config_value_opt("output_dir")) {
+        Some(dir) => dir,
+        None => current_dir().unwrap().to_str().unwrap().to_owned(),
+    };
+    std::fs::create_dir_all(dir.as_str()).unwrap();
+    dir
+}
+
+/// 使用配置的模板生成文件名 (不含扩展名)
+pub(crate) fn file_name(title: &str, bvid: &str) -> String {
+    allowed_file_name(
+        &config::config_value("filename_template")
+            .replace("{title}", title)
+            .replace("{bvid}", bvid),
+    )
+}
+
+/// 选择视频清晰度, 配置为choose时在 (名称, 清晰度) 中选择
+pub(crate) fn choose_video_quality(video_ids: &[i64], formats: Vec<(String, i64)>) -> i64 {
+    match preferred_quality("video_quality", video_ids) {
+        Some(quality) => quality,
+        None => {
+            let choose_string = formats.iter().map(|f| f.0.as_str()).collect_vec();
+            let choose = Select::new()
+                .with_prompt("选择视频质量")
+                .default(0)
+                .items(&choose_string)
+                .interact()
+                .unwrap();
+            formats[choose].1
         }
-        current_page += 1;
-        page = client
-            .collection_video_page(mid, sid, false, current_page, 20)
-            .await
-            .unwrap();
     }
-    println!();
-    println!("全部完成");
-    Ok(())
 }
 
-async fn down_fav_list(fid: i64) -> crate::Result<()> {
-    let client = login_client().await?;
-    let mut current_page = 1;
-    let page_size = 20;
-    loop {
-        let page = client
-            .fav_list_page(fid, current_page, page_size, None, FavListOrder::Mtime)
-            .await
-            .unwrap();
-        println!();
-        println!("获取到收藏夹 : {} : 第{}页", page.info.title, current_page);
-        println!();
-        let folder = allowed_file_name(page.info.title.as_str());
-        std::fs::create_dir_all(folder.as_str()).unwrap();
-        // 下载视频
-        for archive in page.medias {
-            println!();
-            println!("{}", archive.title);
-            let name = if archive.page == 0 {
-                allowed_file_name(&format!("{} - {}", archive.title, archive.intro))
-            } else {
-                allowed_file_name(&archive.title)
-            };
-            let audio_name = format!("{}.audio", name);
-            let video_name = format!("{}.video", name);
-            let final_name = format!("{}.mp4", name);
-            let audio_file = join_paths(vec![folder.as_str(), audio_name.as_str()]);
-            let video_file = join_paths(vec![folder.as_str(), video_name.as_str()]);
-            let final_file = join_paths(vec![folder.as_str(), final_name.as_str()]);
-            if Path::new(&&final_file).exists() {
-                continue;
-            }
-            //
-            let info = client.bv_info(archive.bvid).await.unwrap();
-            let video_url = client
-                .bv_download_url(info.bvid, info.cid, FNVAL_DASH, VIDEO_QUALITY_4K)
-                .await
+/// 选择音频质量, 配置为choose时选择
+pub(crate) fn choose_audio_quality(audio_ids: &[i64]) -> i64 {
+    match preferred_quality("audio_quality", audio_ids) {
+        Some(quality) => quality,
+        None => {
+            let choose_string = audio_ids.iter().map(|id| audio_name(*id)).collect_vec();
+            let choose = Select::new()
+                .with_prompt("选择音频质量")
+                .default(0)
+                .items(&choose_string)
+                .interact()
                 .unwrap();
-            let audio_url = video_url.dash.audio.first().unwrap().base_url.as_str();
-            let video_url = video_url.dash.video.first().unwrap().base_url.as_str();
-            //
-            down_file_to(audio_url, &audio_file, "下载音频").await;
-            println!(" > 下载音频");
-            down_file_to(video_url, &video_file, "下载视频").await;
-            println!(" > 下载视频");
-            println!(" > 合并视频");
-            ffmpeg::ffmpeg_merge_file(vec![&video_file, &audio_file], &final_file).unwrap();
-            println!(" > 清理合并前的数据");
-            let _ = std::fs::remove_file(&audio_file);
-            let _ = std::fs::remove_file(&video_file);
+            audio_ids[choose]
         }
-        // 获取下一页
-        if page.has_more {
-            current_page += 1;
-        } else {
-            break;
+    }
+}
+
+/// 不能选择时使用的清晰度, 配置为choose时使用最高的清晰度
+pub(crate) fn default_quality(key: &str, ids: &[i64]) -> Option<i64> {
+    preferred_quality(key, ids).or_else(|| ids.iter().max().copied())
+}
+
+/// 视频清晰度的名称
+pub(crate) fn video_name(id: i64) -> String {
+    match id {
+        127 => "8K 超高清".to_owned(),
+        126 => "杜比视界".to_owned(),
+        125 => "HDR 真彩".to_owned(),
+        120 => "4K 超清".to_owned(),
+        116 => "1080P60 高帧率".to_owned(),
+        112 => "1080P+ 高码率".to_owned(),
+        80 => "1080P 高清".to_owned(),
+        74 => "720P60 高帧率".to_owned(),
+        64 => "720P 高清".to_owned(),
+        32 => "480P 清晰".to_owned(),
+        16 => "360P 流畅".to_owned(),
+        _ => format!("VIDEO-{}", id),
+    }
+}
+
+/// 音频质量的名称
+fn audio_name(id: i64) -> String {
+    match id {
+        30216 => "64K".to_owned(),
+        30232 => "132K".to_owned(),
+        30280 => "192K".to_owned(),
+        _ => format!("AUDIO-{}", id),
+    }
+}
+
+/// 按照配置选择清晰度, 配置为choose时返回None
+/// 配置为数字时选择不超过这个数字的最高清晰度, 都超过时选择最低的
+fn preferred_quality(key: &str, ids: &[i64]) -> Option<i64> {
+    match config::config_value(key).as_str() {
+        "choose" => None,
+        "best" => ids.iter().max().copied(),
+        value => {
+            let limit: i64 = value.parse().unwrap_or(i64::MAX);
+            ids.iter()
+                .filter(|id| **id <= limit)
+                .max()
+                .or_else(|| ids.iter().min())
+                .copied()
         }
     }
+}
+
+/// 按照配置的并发数下载多个视频 (bvid, 标题)
+async fn down_archives(
+    client: &WebApi,
+    folder: &str,
+    archives: Vec<(String, String)>,
+) -> crate::Result<()> {
+    futures::stream::iter(archives.into_iter().map(|(bvid, title)| async move {
+        print_line("");
+        print_line(title.as_str());
+        let name = file_name(&title, &bvid);
+        down_dash_archive(client, bvid, None, folder, &name).await
+    }))
+    .buffer_unordered(config::concurrency())
+    .try_collect::<Vec<()>>()
+    .await?;
+    Ok(())
+}
+
+/// 使用配置的清晰度下载DASH音视频并合并到文件夹, 合并后的文件已经存在时跳过
+/// cid为空时使用第一个分P
+pub(crate) async fn down_dash_archive(
+    client: &WebApi,
+    bvid: String,
+    cid: Option<i64>,
+    folder: &str,
+    name: &str,
+) -> crate::Result<()> {
+    if Path::new(&merged_file(folder, name)).exists() {
+        return Ok(());
+    }
+    let cid = match cid {
+        Some(cid) => cid,
+        None => client.archive_view(&bvid).await?.cid,
+    };
+    let video_url = client
+        .play_url(&bvid, cid, FNVAL_DASH, VIDEO_QUALITY_4K)
+        .await?;
+    let audio = video_url
+        .dash
+        .audio
+        .iter()
+        .map(|x| (x.id, x.base_url.clone()))
+        .collect_vec();
+    let video = video_url
+        .dash
+        .video
+        .iter()
+        .map(|x| (x.id, x.base_url.clone()))
+        .collect_vec();
+    down_dash_media(audio, video, &bvid, cid, folder, name).await
+}
+
+/// 使用地区的接口服务器下载有地区限制的番剧
+pub(crate) async fn down_region_archive(
+    web_api: &WebApi,
+    region: Region,
+    aid: i64,
+    bvid: &str,
+    cid: i64,
+    folder: &str,
+    name: &str,
+) -> crate::Result<()> {
+    if Path::new(&merged_file(folder, name)).exists() {
+        return Ok(());
+    }
+    let endpoint = region
+        .endpoint()
+        .with_context(|| format!("没有配置 {}", region.config_key()))?;
+    let (audio, video) = region::region_dash(web_api, region, &endpoint, aid, bvid, cid).await?;
+    down_dash_media(audio, video, bvid, cid, folder, name).await
+}
+
+/// 合并后的文件
+pub(crate) fn merged_file(folder: &str, name: &str) -> String {
+    join_paths(vec![folder, format!("{}.mp4", name).as_str()])
+}
+
+/// 从 (质量, 地址) 中按照配置的清晰度选择音频和视频, 下载并合并
+async fn down_dash_media(
+    audio: Vec<(i64, String)>,
+    video: Vec<(i64, String)>,
+    bvid: &str,
+    cid: i64,
+    folder: &str,
+    name: &str,
+) -> crate::Result<()> {
+    let audio_name = format!("{}.audio", name);
+    let video_name = format!("{}.video", name);
+    let audio_file = join_paths(vec![folder, audio_name.as_str()]);
+    let video_file = join_paths(vec![folder, video_name.as_str()]);
+    let final_file = merged_file(folder, name);
+    let audio_ids = audio.iter().map(|x| x.0).collect_vec();
+    let video_ids = video.iter().map(|x| x.0).collect_vec();
+    let audio_quality =
+        default_quality("audio_quality", &audio_ids).with_context(|| "未找到音频")?;
+    let video_quality =
+        default_quality("video_quality", &video_ids).with_context(|| "未找到视频")?;
+    let audio_url = audio
+        .iter()
+        .find(|x| x.0 == audio_quality)
+        .unwrap()
+        .1
+        .as_str();
+    let video_url = video
+        .iter()
+        .find(|x| x.0 == video_quality)
+        .unwrap()
+        .1
+        .as_str();
+    //
+    down_file_to(audio_url, &audio_file, "下载音频").await?;
+    print_line(" > 下载音频");
+    down_file_to(video_url, &video_file, "下载视频").await?;
+    print_line(" > 下载视频");
+    let view_points = chapter::fetch_view_points(bvid, cid, chapter::need_chapters()).await;
+    let chapters = chapter::chapters(view_points.as_deref());
+    print_line(" > 合并视频");
+    merge_file_blocking(&video_file, &audio_file, &chapters, &final_file).await?;
+    chapter::save_chapters_file(&chapters, &final_file);
+    print_line(" > 清理合并前的数据");
+    let _ = std::fs::remove_file(&audio_file);
+    let _ = std::fs::remove_file(&video_file);
+    cover::after_download(bvid, view_points.as_deref(), &final_file).await;
     Ok(())
 }
 
-async fn down_file_to(url: &str, path: &str, title: &str) {
+/// 在阻塞线程中运行合并和转码等耗时的操作, 不阻塞同时下载的其他视频
+pub(crate) async fn run_blocking<T, F>(f: F) -> crate::Result<T>
+where
+    F: FnOnce() -> crate::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+/// 在阻塞线程中合并视频和音频
+async fn merge_file_blocking(
+    video_file: &str,
+    audio_file: &str,
+    chapters: &[chapter::Chapter],
+    output: &str,
+) -> crate::Result<()> {
+    let (video_file, audio_file, output) = (
+        video_file.to_owned(),
+        audio_file.to_owned(),
+        output.to_owned(),
+    );
+    let chapters = chapters.to_vec();
+    run_blocking(move || transcode::merge_file(&video_file, &audio_file, &chapters, &output)).await
+}
+
+async fn down_file_to(url: &str, path: &str, title: &str) -> crate::Result<()> {
     let path = Path::new(path);
     let checkpoint = if app::resume_download_value() && path.exists() {
-        path.metadata().unwrap().len()
+        path.metadata()?.len()
     } else {
         0
     };
-    let rsp = request_resource(url).await;
-    let size = content_length(&rsp).unwrap();
+    let rsp = request_resource(url).await?;
+    let size = content_length(&rsp)?;
     let (rsp, file) = if checkpoint == 0 {
-        (rsp, tokio::fs::File::create(path).await.unwrap())
+        (rsp, tokio::fs::File::create(path).await?)
     } else {
         if size == checkpoint {
-            return;
+            return Ok(());
         }
         drop(rsp);
         (
-            request_resource_rang(url, checkpoint).await,
+            request_resource_rang(url, checkpoint).await?,
             tokio::fs::OpenOptions::new()
                 .append(true)
                 .open(path)
-                .await
-                .unwrap(),
+                .await?,
         )
     };
     let mut file = BufWriter::with_capacity(1 << 18, file);
@@ -487,18 +934,26 @@ async fn down_file_to(url: &str, path: &str, title: &str) {
         StreamReader::new(rsp.bytes_stream().map_err(convert_error)),
     );
     let (sender, mut receiver) = tokio::sync::mpsc::channel::<Vec<u8>>(1 << 10);
+    // 每次读取的超时, 不限制整个文件的下载时间
+    let read_timeout = http::read_timeout();
     let sjb = tokio::spawn(async move {
         loop {
-            let read = reader.read(buf.as_mut()).await.unwrap();
+            let read = tokio::time::timeout(read_timeout, reader.read(buf.as_mut()))
+                .await
+                .map_err(|_| anyhow::Error::msg("读取超时"))??;
             if read == 0 {
                 break;
             }
-            sender.send(buf[0..read].to_vec()).await.unwrap();
+            // 写入失败时接收端已经退出, 错误由接收端返回
+            if sender.send(buf[0..read].to_vec()).await.is_err() {
+                break;
+            }
         }
+        Ok::<(), anyhow::Error>(())
     });
     let title = title.to_string();
     let rjb = tokio::spawn(async move {
-        let pb = ProgressBar::new(size);
+        let pb = MULTI_PROGRESS.add(ProgressBar::new(size));
         pb.set_style(
             ProgressStyle::default_bar()
                 .template(
@@ -512,35 +967,50 @@ async fn down_file_to(url: &str, path: &str, title: &str) {
         );
         let mut down_count: u64 = checkpoint;
         pb.set_position(down_count);
+        let mut last_progress = Instant::now();
         while let Some(msg) = receiver.recv().await {
-            file.write(&msg).await.unwrap();
-            down_count = down_count + msg.len() as u64;
+            if let Err(err) = file.write_all(&msg).await {
+                pb.finish_and_clear();
+                return Err(err.into());
+            }
+            down_count += msg.len() as u64;
             pb.set_position(down_count);
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                send_progress(title.as_str(), down_count, size);
+            }
         }
         pb.finish_and_clear();
-        file.flush().await.unwrap();
+        file.flush().await?;
+        Ok::<(), anyhow::Error>(())
     });
     let (s, r) = tokio::join!(sjb, rjb);
-    s.unwrap();
-    r.unwrap();
+    s??;
+    r??;
+    Ok(())
 }
 
 fn convert_error(err: reqwest::Error) -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::Other, err)
 }
 
-async fn request_resource(url: &str) -> reqwest::Response {
-    reqwest::Client::new().get(url).header(
-        "user-agent",
-        "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Safari/537.36",
-    ).header("referer", "https://www.bilibili.com").send().await.unwrap().error_for_status().unwrap()
+async fn request_resource(url: &str) -> crate::Result<reqwest::Response> {
+    Ok(http::http_client()
+        .get(url)
+        .header("referer", "https://www.bilibili.com")
+        .send()
+        .await?
+        .error_for_status()?)
 }
 
-async fn request_resource_rang(url: &str, begin: u64) -> reqwest::Response {
-    reqwest::Client::new().get(url).header(
-        "user-agent",
-        "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Safari/537.36",
-    ).header("referer", "https://www.bilibili.com").header("Range",format!("bytes={}-",begin)).send().await.unwrap().error_for_status().unwrap()
+async fn request_resource_rang(url: &str, begin: u64) -> crate::Result<reqwest::Response> {
+    Ok(http::http_client()
+        .get(url)
+        .header("referer", "https://www.bilibili.com")
+        .header("Range", format!("bytes={}-", begin))
+        .send()
+        .await?
+        .error_for_status()?)
 }
 
 fn content_length(rsp: &reqwest::Response) -> crate::Result<u64> {
@@ -553,3 +1023,47 @@ fn content_length(rsp: &reqwest::Response) -> crate::Result<u64> {
         .parse()
         .with_context(|| "未能取得文件长度, HEADER不能识别未数字")?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_all() {
+        assert_eq!(select_indexes(3, None, None, false), vec![0, 1, 2]);
+        assert_eq!(select_indexes(0, None, Some(2), true), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn select_ranges() {
+        // 范围从1开始, 超出总数的部分被忽略
+        assert_eq!(
+            select_indexes(20, Some(vec![(2, 3), (13, usize::MAX)]), None, false),
+            vec![1, 2, 12, 13, 14, 15, 16, 17, 18, 19]
+        );
+        assert_eq!(
+            select_indexes(3, Some(vec![(5, 8)]), None, false),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn select_latest() {
+        // 从旧到新排列时最新的在最后
+        assert_eq!(select_indexes(5, None, Some(2), false), vec![3, 4]);
+        // 从新到旧排列时最新的在最前
+        assert_eq!(select_indexes(5, None, Some(2), true), vec![0, 1]);
+        assert_eq!(select_indexes(2, None, Some(5), false), vec![0, 1]);
+    }
+
+    #[test]
+    fn select_latest_in_ranges() {
+        // --latest 在 --items 选择的范围中再选择最新的
+        let ranges = Some(vec![(1, 3), (6, 8)]);
+        assert_eq!(
+            select_indexes(10, ranges.clone(), Some(2), false),
+            vec![6, 7]
+        );
+        assert_eq!(select_indexes(10, ranges, Some(2), true), vec![0, 1]);
+    }
+}
@@ -1 +1,4 @@
-pub(crate) mod property;
\ No newline at end of file
+pub(crate) mod property;
+pub(crate) mod subscription;
+pub(crate) mod sync_item;
+pub(crate) mod tracked_season;
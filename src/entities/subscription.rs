@@ -0,0 +1,21 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, EntityTrait};
+
+/// watch定时检查的订阅
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "subscription")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub url: String,
+    /// user / fav / collection / bangumi
+    pub kind: String,
+    /// 检查的间隔(秒), 0为使用配置中的watch_interval
+    pub interval: i64,
+    /// 上次检查的时间(秒)
+    pub last_checked: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
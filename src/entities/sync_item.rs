@@ -0,0 +1,30 @@
+use crate::local::{create_index, index_exists};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, EntityTrait};
+
+/// 同步的收藏夹或合集中已经下载的视频
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "sync_item")]
+pub struct Model {
+    /// 来源, 例如 fav:123 / collection:456:789
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub source: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub bvid: String,
+    pub title: String,
+    /// 下载到的文件
+    pub file: String,
+    /// 同步的时间(秒)
+    pub synced_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub(crate) async fn init_indexes(db: &DatabaseConnection) {
+    if !index_exists(db, "sync_item", "idx_sync_item_source").await {
+        create_index(db, "sync_item", vec!["source"], "idx_sync_item_source").await;
+    }
+}
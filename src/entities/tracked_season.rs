@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, EntityTrait};
+
+/// 追番, 记录已经下载到的剧集
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "tracked_season")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub season_id: i64,
+    pub title: String,
+    /// 最后一个已经下载的剧集的ep_id, 0为还没有下载
+    pub last_episode_id: i64,
+    /// 上次更新的时间(秒)
+    pub updated_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,54 +1,227 @@
-#[cfg(not(feature = "ffmpeg_api"))]
+use std::io::{BufRead, BufReader};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
 
-#[cfg(feature = "ffmpeg_api")]
-pub(crate) fn ffmpeg_run_version() -> crate::Result<()> {
-    Ok(())
-}
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::chapter::{self, Chapter};
+use crate::config;
+use crate::down::MULTI_PROGRESS;
+use crate::mux::Muxer;
 
-#[cfg(not(feature = "ffmpeg_api"))]
-pub(crate) fn ffmpeg_run_version() -> crate::Result<()> {
-    let mut cmd = Command::new("ffmpeg");
-    cmd.stderr(Stdio::null());
-    cmd.stdout(Stdio::null());
-    cmd.arg("-version");
-    match cmd.status() {
-        Ok(_) => Ok(()),
-        Err(_) => Err(anyhow::Error::msg("未找到ffmpeg, 请先安装ffmpeg.")),
+/// 失败时显示的ffmpeg错误输出的行数
+const STDERR_TAIL_LINES: usize = 10;
+
+/// 调用ffmpeg命令合并
+pub(crate) struct FfmpegMuxer;
+
+impl Muxer for FfmpegMuxer {
+    fn name(&self) -> &'static str {
+        "ffmpeg"
     }
-}
 
-#[cfg(feature = "ffmpeg_api")]
-pub(crate) fn ffmpeg_merge_file(list: Vec<&str>, output: &str) -> bilirust::Result<()> {
-    ffmpeg_api::ffmpeg_merge_files(list, output)
+    fn available(&self) -> bool {
+        let mut cmd = Command::new(config::ffmpeg_path());
+        cmd.stderr(Stdio::null());
+        cmd.stdout(Stdio::null());
+        cmd.arg("-version");
+        matches!(cmd.status(), Ok(status) if status.success())
+    }
+
+    /// 合并音频视频, 有章节时通过元数据文件写入
+    fn merge(
+        &self,
+        video: &str,
+        audio: &str,
+        chapters: &[Chapter],
+        output: &str,
+    ) -> crate::Result<()> {
+        if chapters.is_empty() {
+            return run_ffmpeg(
+                &[
+                    "-i", video, "-i", audio, "-vcodec", "copy", "-acodec", "copy", output,
+                ],
+                "合并视频",
+            );
+        }
+        let metadata = format!("{}.ffmetadata", output);
+        std::fs::write(metadata.as_str(), chapter::ffmetadata(chapters))?;
+        let result = run_ffmpeg(
+            &[
+                "-i",
+                video,
+                "-i",
+                audio,
+                "-i",
+                metadata.as_str(),
+                "-map",
+                "0:v",
+                "-map",
+                "1:a",
+                "-map_chapters",
+                "2",
+                "-vcodec",
+                "copy",
+                "-acodec",
+                "copy",
+                output,
+            ],
+            "合并视频",
+        );
+        let _ = std::fs::remove_file(metadata.as_str());
+        result
+    }
 }
 
-/// 合并音频视频
-#[cfg(not(feature = "ffmpeg_api"))]
-pub(crate) fn ffmpeg_merge_file(list: Vec<&str>, output: &str) -> bilirust::Result<()> {
-    let mut cmd = Command::new("ffmpeg");
-    cmd.stderr(Stdio::null());
-    cmd.stdout(Stdio::null());
-    for x in list {
-        cmd.arg("-i");
-        cmd.arg(x);
+/// 运行ffmpeg, 通过 -progress 显示进度, 失败时返回ffmpeg的错误输出
+/// 超过ffmpeg_timeout没有进度时结束ffmpeg
+pub(crate) fn run_ffmpeg(args: &[&str], title: &str) -> crate::Result<()> {
+    let path = config::ffmpeg_path();
+    let mut cmd = Command::new(path.as_str());
+    cmd.args([
+        "-hide_banner",
+        "-nostdin",
+        "-nostats",
+        "-progress",
+        "pipe:1",
+    ]);
+    cmd.args(args);
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let mut child = cmd
+        .spawn()
+        .map_err(|err| anyhow::Error::msg(format!("未能运行ffmpeg : {} : {}", path, err)))?;
+    // 错误输出, 同时从中读取输入文件的时长(微秒)
+    let duration = Arc::new(AtomicU64::new(0));
+    let stderr = child.stderr.take().unwrap();
+    let stderr_duration = duration.clone();
+    let stderr_lines = Arc::new(Mutex::new(Vec::<String>::new()));
+    let stderr_thread_lines = stderr_lines.clone();
+    let stderr_thread = std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if let Some(us) = parse_duration(line.as_str()) {
+                stderr_duration.fetch_max(us, Ordering::Relaxed);
+            }
+            stderr_thread_lines.lock().unwrap().push(line);
+        }
+    });
+    // 进度
+    let stdout = child.stdout.take().unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let pb = MULTI_PROGRESS.add(ProgressBar::new(0));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                &("{spinner:.green}  ".to_owned()
+                    + title
+                    + " [{wide_bar:.cyan/blue}] {percent}% ({elapsed})"),
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    let timeout = config::ffmpeg_timeout();
+    let mut timed_out = false;
+    loop {
+        let line = match timeout {
+            Some(timeout) => match receiver.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    timed_out = true;
+                    let _ = child.kill();
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match receiver.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            },
+        };
+        // 旧版本的ffmpeg中out_time_ms的单位也是微秒
+        let out_time = line
+            .strip_prefix("out_time_us=")
+            .or_else(|| line.strip_prefix("out_time_ms="));
+        if let Some(Ok(us)) = out_time.map(|x| x.trim().parse::<u64>()) {
+            pb.set_length(duration.load(Ordering::Relaxed).max(us));
+            pb.set_position(us);
+        }
     }
-    cmd.arg("-vcodec");
-    cmd.arg("copy");
-    cmd.arg("-acodec");
-    cmd.arg("copy");
-    cmd.arg(output);
-    let status = cmd.status().unwrap();
-    if status.code().unwrap() == 0 {
+    pb.finish_and_clear();
+    let status = child.wait()?;
+    // 结束ffmpeg时不等待错误输出读取完成, 避免ffmpeg的子进程仍然持有输出时等待
+    if !timed_out {
+        let _ = stderr_thread.join();
+    }
+    let stderr = stderr_lines.lock().unwrap();
+    let stderr_tail = stderr
+        .iter()
+        .skip(stderr.len().saturating_sub(STDERR_TAIL_LINES))
+        .map(|line| line.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if timed_out {
+        return Err(anyhow::Error::msg(format!(
+            "ffmpeg超过 {} 秒没有进度, 已经结束, 可以修改 ffmpeg_timeout 配置\n{}",
+            timeout.unwrap().as_secs(),
+            stderr_tail
+        )));
+    }
+    if status.success() {
         Ok(())
     } else {
         Err(anyhow::Error::msg(format!(
-            "FFMPEG 未能成功运行 : EXIT CODE : {}",
-            status.code().unwrap()
+            "FFMPEG 未能成功运行 : EXIT CODE : {}\n{}",
+            status.code().unwrap_or(-1),
+            stderr_tail
         )))
     }
 }
 
+/// 读取ffmpeg输出中输入文件的时长, 例如 "  Duration: 00:23:40.05, start: 0.000000", 返回微秒
+fn parse_duration(line: &str) -> Option<u64> {
+    let time = line.trim().strip_prefix("Duration: ")?.split(',').next()?;
+    let mut parts = time.split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(((hours * 3600.0 + minutes * 60.0 + seconds) * 1_000_000.0) as u64)
+}
+
+/// 使用链接的libav合并
+#[cfg(feature = "ffmpeg_api")]
+pub(crate) struct LibavMuxer;
+
+#[cfg(feature = "ffmpeg_api")]
+impl Muxer for LibavMuxer {
+    fn name(&self) -> &'static str {
+        "libav"
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn merge(
+        &self,
+        video: &str,
+        audio: &str,
+        chapters: &[Chapter],
+        output: &str,
+    ) -> crate::Result<()> {
+        ffmpeg_api::ffmpeg_merge_files(vec![video, audio], chapters, output)
+    }
+}
+
 #[cfg(feature = "ffmpeg_api")]
 mod ffmpeg_api {
     use anyhow::{anyhow, Context};
@@ -56,12 +229,19 @@ mod ffmpeg_api {
         self,
         avcodec::{AVCodec, AVCodecContext},
         avformat::{AVFormatContextInput, AVFormatContextOutput},
+        ffi,
     };
     use std::collections::HashMap;
     use std::ffi::CString;
-    use std::os::raw::c_int;
+    use std::os::raw::{c_int, c_void};
 
-    pub fn ffmpeg_merge_files(list: Vec<&str>, output: &str) -> anyhow::Result<()> {
+    use crate::chapter::Chapter;
+
+    pub fn ffmpeg_merge_files(
+        list: Vec<&str>,
+        chapters: &[Chapter],
+        output: &str,
+    ) -> anyhow::Result<()> {
         let output = CString::new(output)?;
         let mut output_format_context = AVFormatContextOutput::create(&output, None)?;
         let mut inputs = vec![];
@@ -87,6 +267,7 @@ mod ffmpeg_api {
             }
             inputs.push((input_format_context, stream_index_map));
         }
+        set_chapters(&mut output_format_context, chapters)?;
         let mut dict = None;
         output_format_context.write_header(&mut dict)?;
         for (mut input_format_context, stream_index_map) in inputs {
@@ -95,18 +276,69 @@ mod ffmpeg_api {
                     Some(x) => x,
                     None => break,
                 };
-                packet.set_stream_index(
-                    stream_index_map
-                        .get(&(packet.stream_index as i32))
-                        .unwrap()
-                        .clone() as c_int,
-                );
-                output_format_context
-                    .interleaved_write_frame(&mut packet)
-                    .unwrap();
+                let stream_index = *stream_index_map
+                    .get(&(packet.stream_index as i32))
+                    .ok_or_else(|| {
+                        anyhow!("output stream of ({}) not found.", packet.stream_index)
+                    })?;
+                packet.set_stream_index(stream_index as c_int);
+                output_format_context.interleaved_write_frame(&mut packet)?;
             }
         }
         output_format_context.write_trailer()?;
         Ok(())
     }
+
+    /// 在write_header之前添加章节, 添加到已有的章节之后, 章节和标题由avformat_free_context释放
+    /// rsmpeg没有添加章节的接口, 和libavformat一样使用av_dynarray_add追加到chapters
+    fn set_chapters(
+        output_format_context: &mut AVFormatContextOutput,
+        chapters: &[Chapter],
+    ) -> anyhow::Result<()> {
+        if chapters.is_empty() {
+            return Ok(());
+        }
+        let key = CString::new("title")?;
+        let titles = chapters
+            .iter()
+            .map(|chapter| CString::new(chapter.title.replace('\0', "")))
+            .collect::<Result<Vec<CString>, _>>()?;
+        unsafe {
+            let context = output_format_context.as_mut_ptr();
+            for (index, chapter) in chapters.iter().enumerate() {
+                let item =
+                    ffi::av_mallocz(std::mem::size_of::<ffi::AVChapter>()) as *mut ffi::AVChapter;
+                if item.is_null() {
+                    return Err(anyhow!("av_mallocz failed"));
+                }
+                // 每追加一个章节nb_chapters加一, 当前的数量就是新章节的序号
+                (*item).id = (*context).nb_chapters as _;
+                (*item).time_base = ffi::AVRational { num: 1, den: 1000 };
+                (*item).start = chapter.start as i64;
+                (*item).end = chapter.end as i64;
+                let ret = ffi::av_dict_set(
+                    &mut (*item).metadata,
+                    key.as_ptr(),
+                    titles[index].as_ptr(),
+                    0,
+                );
+                // 追加成功之后章节属于context, 失败时只需要释放当前的章节
+                let ret = if ret < 0 {
+                    ret
+                } else {
+                    ffi::av_dynarray_add_nofree(
+                        &mut (*context).chapters as *mut _ as *mut c_void,
+                        &mut (*context).nb_chapters as *mut _ as *mut c_int,
+                        item as *mut c_void,
+                    )
+                };
+                if ret < 0 {
+                    ffi::av_dict_free(&mut (*item).metadata);
+                    ffi::av_free(item as *mut c_void);
+                    return Err(anyhow!("未能添加章节 : {}", ret));
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,81 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use once_cell::sync::OnceCell;
+
+use crate::config::{config_value, config_value_opt};
+
+/// 没有配置user_agent时使用的User-Agent
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Safari/537.36";
+
+/// 共享的HTTP客户端
+static HTTP_CLIENT: OnceCell<reqwest::Client> = OnceCell::new();
+
+/// 不跟随重定向的HTTP客户端, 用于解析短链接
+static NO_REDIRECT_CLIENT: OnceCell<reqwest::Client> = OnceCell::new();
+
+/// 按照配置创建HTTP客户端, 需要在init_config之后调用
+pub(crate) fn init_http() -> crate::Result<()> {
+    let _ = HTTP_CLIENT.set(client_builder()?.build()?);
+    let _ = NO_REDIRECT_CLIENT.set(
+        client_builder()?
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?,
+    );
+    Ok(())
+}
+
+fn client_builder() -> crate::Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::ClientBuilder::new()
+        .user_agent(user_agent())
+        .connect_timeout(seconds("connect_timeout"));
+    if let Some(proxy) = config_value_opt("proxy") {
+        let proxy = reqwest::Proxy::all(proxy.as_str()).map_err(|err| {
+            anyhow::Error::msg(format!("代理服务器配置错误 : {} : {}", proxy, err))
+        })?;
+        builder = builder.proxy(proxy);
+    }
+    match config_value("ip_version").as_str() {
+        "4" => builder = builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        "6" => builder = builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        _ => (),
+    }
+    if let Some(ca_file) = config_value_opt("ca_file") {
+        let pem = std::fs::read(ca_file.as_str())
+            .map_err(|err| anyhow::Error::msg(format!("未能读取证书 : {} : {}", ca_file, err)))?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    Ok(builder)
+}
+
+fn seconds(key: &str) -> Duration {
+    Duration::from_secs(config_value(key).parse().unwrap_or(30))
+}
+
+/// 共享的HTTP客户端
+pub(crate) fn http_client() -> reqwest::Client {
+    HTTP_CLIENT.get().expect("HTTP客户端未初始化").clone()
+}
+
+/// 不跟随重定向的HTTP客户端
+pub(crate) fn no_redirect_client() -> reqwest::Client {
+    NO_REDIRECT_CLIENT
+        .get()
+        .expect("HTTP客户端未初始化")
+        .clone()
+}
+
+/// 配置的User-Agent
+pub(crate) fn user_agent() -> String {
+    config_value_opt("user_agent").unwrap_or_else(|| DEFAULT_USER_AGENT.to_owned())
+}
+
+/// 读取超时, 下载时超过这个时间没有收到数据时失败
+pub(crate) fn read_timeout() -> Duration {
+    seconds("read_timeout")
+}
+
+/// 请求超时, 接口和图片的请求超过这个时间没有完成时失败
+pub(crate) fn request_timeout() -> Duration {
+    seconds("request_timeout")
+}
@@ -2,10 +2,17 @@ use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
 use async_once::AsyncOnce;
+use dialoguer::Password;
+use hmac::Hmac;
 use lazy_static::lazy_static;
-use sea_orm::{ActiveModelTrait, EntityTrait, Set};
-use sea_orm::{ConnectionTrait, DatabaseConnection, Schema, Statement};
+use once_cell::sync::OnceCell;
+use rand::RngCore;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use sea_orm::{ConnectionTrait, DatabaseConnection, Schema, Statement, TransactionTrait};
+use sha2::Sha256;
 use tokio::sync::Mutex;
 
 use crate::entities::*;
@@ -27,7 +34,7 @@ pub(crate) fn join_paths<P: AsRef<Path>>(paths: Vec<P>) -> String {
 
 /// 取配置文件目录
 #[cfg(target_os = "macos")]
-fn cfg_local_dir() -> String {
+pub(crate) fn cfg_local_dir() -> String {
     join_paths(vec![
         dirs::home_dir().unwrap().to_str().unwrap(),
         "Library",
@@ -38,7 +45,7 @@ fn cfg_local_dir() -> String {
 
 /// 取配置文件目录
 #[cfg(target_os = "windows")]
-fn cfg_local_dir() -> String {
+pub(crate) fn cfg_local_dir() -> String {
     join_paths(vec![
         dirs::home_dir().unwrap().to_str().unwrap(),
         "AppData",
@@ -47,51 +54,106 @@ fn cfg_local_dir() -> String {
     ])
 }
 
-/// 取配置文件目录
+/// 取配置文件目录, 遵循XDG规范, 默认为 ~/.config/bili-cli
 #[cfg(target_os = "linux")]
-fn cfg_local_dir() -> String {
-    join_paths(vec![
-        dirs::home_dir().unwrap().to_str().unwrap(),
-        ".bili-cli",
-    ])
+pub(crate) fn cfg_local_dir() -> String {
+    xdg_dir("XDG_CONFIG_HOME", vec![".config"])
 }
 
 /// 取配置文件目录
 #[cfg(target_os = "android")]
-fn cfg_local_dir() -> String {
+pub(crate) fn cfg_local_dir() -> String {
     join_paths(vec![
         dirs::home_dir().unwrap().to_str().unwrap(),
         ".bili-cli",
     ])
 }
 
-/// 取临时文件目录
-#[cfg(target_os = "macos")]
-pub(crate) fn template_dir() -> String {
-    "/tmp".to_owned()
+/// 取数据目录, 遵循XDG规范, 默认为 ~/.local/share/bili-cli
+#[cfg(target_os = "linux")]
+pub(crate) fn data_local_dir() -> String {
+    xdg_dir("XDG_DATA_HOME", vec![".local", "share"])
+}
+
+/// 取数据目录
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn data_local_dir() -> String {
+    cfg_local_dir()
 }
 
-/// 取临时文件目录
+/// 环境变量中的目录, 未设置或者不是绝对路径时使用家目录下的默认目录
 #[cfg(target_os = "linux")]
-pub(crate) fn template_dir() -> String {
-    "/tmp".to_owned()
+fn xdg_dir(env: &str, default: Vec<&str>) -> String {
+    let base = match std::env::var(env) {
+        Ok(dir) if Path::new(dir.as_str()).is_absolute() => dir,
+        _ => {
+            let mut paths = vec![dirs::home_dir().unwrap().to_str().unwrap().to_owned()];
+            paths.extend(default.into_iter().map(str::to_owned));
+            join_paths(paths)
+        }
+    };
+    join_paths(vec![base.as_str(), "bili-cli"])
 }
 
-/// 取临时文件目录
-#[cfg(target_os = "windows")]
-pub(crate) fn template_dir() -> String {
-    std::env::temp_dir().to_str().unwrap().to_owned()
+/// 旧版本使用的配置文件目录
+#[cfg(target_os = "linux")]
+fn legacy_local_dir() -> String {
+    join_paths(vec![
+        dirs::home_dir().unwrap().to_str().unwrap(),
+        ".bili-cli",
+    ])
 }
 
-/// 取临时文件目录
-#[cfg(target_os = "android")]
+/// 将旧版本 ~/.bili-cli 中的文件移动到新的目录, config.toml移动到配置文件目录, 其他移动到数据目录
+#[cfg(target_os = "linux")]
+fn migrate_legacy_dir() {
+    let legacy = legacy_local_dir();
+    let entries = match std::fs::read_dir(legacy.as_str()) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let target_dir = if name == "config.toml" {
+            cfg_local_dir()
+        } else {
+            data_local_dir()
+        };
+        let target = join_paths(vec![target_dir.as_str(), name.to_str().unwrap()]);
+        if Path::new(target.as_str()).exists() {
+            continue;
+        }
+        // 跨文件系统时rename会失败, 复制后删除
+        if std::fs::rename(entry.path(), target.as_str()).is_err()
+            && std::fs::copy(entry.path(), target.as_str()).is_ok()
+        {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    if std::fs::remove_dir(legacy.as_str()).is_ok() {
+        println!(
+            "已将 {} 迁移到 {} 和 {}",
+            legacy,
+            cfg_local_dir(),
+            data_local_dir()
+        );
+    }
+}
+
+/// 取临时文件目录, 使用 $TMPDIR (windows为 %TEMP%)
 pub(crate) fn template_dir() -> String {
-    "/tmp".to_owned()
+    std::env::temp_dir().to_str().unwrap().to_owned()
 }
 
-/// 初始化配置文件目录
-fn init_dir() {
-    std::fs::create_dir_all(cfg_local_dir()).unwrap();
+/// 初始化配置文件目录和数据目录, 需要时迁移旧版本的目录
+pub(crate) fn init_dir() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        std::fs::create_dir_all(cfg_local_dir()).unwrap();
+        std::fs::create_dir_all(data_local_dir()).unwrap();
+        #[cfg(target_os = "linux")]
+        migrate_legacy_dir();
+    });
 }
 
 /// 连接到Sqlite数据库
@@ -189,10 +251,14 @@ lazy_static! {
         AsyncOnce::new(async {
             init_dir();
             let db =
-                connect_db(join_paths(vec![cfg_local_dir().as_str(), "properties.db"]).as_str())
+                connect_db(join_paths(vec![data_local_dir().as_str(), "properties.db"]).as_str())
                     .await;
             create_table_if_not_exists(&db, property::Entity).await;
             property::init_indexes(&db).await;
+            create_table_if_not_exists(&db, sync_item::Entity).await;
+            sync_item::init_indexes(&db).await;
+            create_table_if_not_exists(&db, subscription::Entity).await;
+            create_table_if_not_exists(&db, tracked_season::Entity).await;
             Mutex::<DatabaseConnection>::new(db)
         });
 }
@@ -208,26 +274,30 @@ pub(crate) async fn load_property_from_db(db: &DatabaseConnection, k: String) ->
     })
 }
 
-/// 从默认数据库读取配置文件
+/// 从默认数据库读取配置文件, 加密保存的配置会被解密
 pub(crate) async fn load_property(k: String) -> Result<String> {
-    load_property_from_db(PROPERTY_DB.get().await.lock().await.deref(), k).await
+    let db = PROPERTY_DB.get().await.lock().await;
+    let v = load_property_from_db(db.deref(), k).await?;
+    match v.strip_prefix(ENCRYPTED_PREFIX) {
+        Some(encrypted) => decrypt(&crypto_key(db.deref()).await?, encrypted),
+        None => Ok(v),
+    }
 }
 
 /// 写入配置文件夹
-pub(crate) async fn save_property_from_db(
-    db: &DatabaseConnection,
+/// db可以为数据库或者事务
+pub(crate) async fn save_property_from_db<C: ConnectionTrait>(
+    db: &C,
     k: String,
     v: String,
 ) -> Result<()> {
-    let in_db = property::Entity::find_by_id(k.clone())
-        .one(db.deref())
-        .await?;
+    let in_db = property::Entity::find_by_id(k.clone()).one(db).await?;
     match in_db {
         Some(in_db) => {
             let mut data: property::ActiveModel = in_db.into();
             data.k = Set(k.clone());
             data.v = Set(v.clone());
-            data.update(db.deref()).await?;
+            data.update(db).await?;
         }
         None => {
             let insert = property::ActiveModel {
@@ -235,15 +305,353 @@ pub(crate) async fn save_property_from_db(
                 v: Set(v.clone()),
                 ..Default::default()
             };
-            insert.insert(db.deref()).await?;
+            insert.insert(db).await?;
         }
     };
     Ok(())
 }
 
-/// 从默认数据库写入配置文件
+/// 从默认数据库写入配置文件, 加密后登录信息会被加密保存
 pub(crate) async fn save_property(k: String, v: String) -> Result<()> {
-    save_property_from_db(PROPERTY_DB.get().await.lock().await.deref(), k, v).await
+    let db = PROPERTY_DB.get().await.lock().await;
+    let v = if is_sensitive_key(k.as_str()) && is_locked(db.deref()).await? {
+        encrypt(&crypto_key(db.deref()).await?, v.as_str())?
+    } else {
+        v
+    };
+    save_property_from_db(db.deref(), k, v).await
+}
+
+/// 读取同步记录
+pub(crate) async fn load_sync_items(source: &str) -> Result<Vec<sync_item::Model>> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    Ok(sync_item::Entity::find()
+        .filter(sync_item::Column::Source.eq(source))
+        .all(db.deref())
+        .await?)
+}
+
+/// 写入同步记录
+pub(crate) async fn save_sync_item(item: sync_item::Model) -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let in_db = sync_item::Entity::find_by_id((item.source.clone(), item.bvid.clone()))
+        .one(db.deref())
+        .await?;
+    match in_db {
+        Some(in_db) => {
+            let mut data: sync_item::ActiveModel = in_db.into();
+            data.title = Set(item.title);
+            data.file = Set(item.file);
+            data.synced_at = Set(item.synced_at);
+            data.update(db.deref()).await?;
+        }
+        None => {
+            let insert: sync_item::ActiveModel = item.into();
+            insert.insert(db.deref()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// 删除同步记录
+pub(crate) async fn delete_sync_item(source: &str, bvid: &str) -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    sync_item::Entity::delete_by_id((source.to_owned(), bvid.to_owned()))
+        .exec(db.deref())
+        .await?;
+    Ok(())
+}
+
+/// 读取全部订阅
+pub(crate) async fn load_subscriptions() -> Result<Vec<subscription::Model>> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    Ok(subscription::Entity::find().all(db.deref()).await?)
+}
+
+/// 写入订阅
+pub(crate) async fn save_subscription(item: subscription::Model) -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let in_db = subscription::Entity::find_by_id(item.url.clone())
+        .one(db.deref())
+        .await?;
+    match in_db {
+        Some(in_db) => {
+            let mut data: subscription::ActiveModel = in_db.into();
+            data.kind = Set(item.kind);
+            data.interval = Set(item.interval);
+            data.last_checked = Set(item.last_checked);
+            data.update(db.deref()).await?;
+        }
+        None => {
+            let insert: subscription::ActiveModel = item.into();
+            insert.insert(db.deref()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// 删除订阅, 返回是否存在
+pub(crate) async fn delete_subscription(url: &str) -> Result<bool> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let rsp = subscription::Entity::delete_by_id(url.to_owned())
+        .exec(db.deref())
+        .await?;
+    Ok(rsp.rows_affected > 0)
+}
+
+/// 读取全部追番
+pub(crate) async fn load_tracked_seasons() -> Result<Vec<tracked_season::Model>> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    Ok(tracked_season::Entity::find().all(db.deref()).await?)
+}
+
+/// 写入追番
+pub(crate) async fn save_tracked_season(item: tracked_season::Model) -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let in_db = tracked_season::Entity::find_by_id(item.season_id)
+        .one(db.deref())
+        .await?;
+    match in_db {
+        Some(in_db) => {
+            let mut data: tracked_season::ActiveModel = in_db.into();
+            data.title = Set(item.title);
+            data.last_episode_id = Set(item.last_episode_id);
+            data.updated_at = Set(item.updated_at);
+            data.update(db.deref()).await?;
+        }
+        None => {
+            let insert: tracked_season::ActiveModel = item.into();
+            insert.insert(db.deref()).await?;
+        }
+    };
+    Ok(())
+}
+
+/// 删除追番, 返回是否存在
+pub(crate) async fn delete_tracked_season(season_id: i64) -> Result<bool> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let rsp = tracked_season::Entity::delete_by_id(season_id)
+        .exec(db.deref())
+        .await?;
+    Ok(rsp.rows_affected > 0)
+}
+
+/// 加密后的配置的前缀
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const CRYPTO_SALT_KEY: &str = "crypto_salt";
+const CRYPTO_CHECK_KEY: &str = "crypto_check";
+const CRYPTO_KEY_FILE_KEY: &str = "crypto_key_file";
+const CRYPTO_CHECK_VALUE: &str = "bili-cli";
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// 无人值守时使用的密码
+pub(crate) const PASSPHRASE_ENV: &str = "BILI_CLI_PASSPHRASE";
+/// 无人值守时使用的密钥文件
+pub(crate) const KEY_FILE_ENV: &str = "BILI_CLI_KEY_FILE";
+
+static CRYPTO_KEY: OnceCell<[u8; 32]> = OnceCell::new();
+
+/// 需要加密保存的配置: 各个账号的登录信息
+fn is_sensitive_key(k: &str) -> bool {
+    ["web_token", "token_meta"]
+        .iter()
+        .any(|prefix| k == *prefix || k.starts_with(format!("{}.", prefix).as_str()))
+}
+
+async fn is_locked(db: &DatabaseConnection) -> Result<bool> {
+    Ok(!load_property_from_db(db, CRYPTO_SALT_KEY.to_owned())
+        .await?
+        .is_empty())
+}
+
+fn derive_key(secret: &[u8], salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(secret, salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+fn encrypt(key: &[u8; 32], v: &str) -> Result<String> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let encrypted = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), v.as_bytes())
+        .map_err(|_| anyhow::Error::msg("加密失败"))?;
+    let mut data = nonce.to_vec();
+    data.extend_from_slice(&encrypted);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, base64::encode(data)))
+}
+
+fn decrypt(key: &[u8; 32], v: &str) -> Result<String> {
+    let data = base64::decode(v)?;
+    if data.len() < 12 {
+        return Err(anyhow::Error::msg("解密失败, 数据已损坏"));
+    }
+    let (nonce, encrypted) = data.split_at(12);
+    let decrypted = Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| anyhow::Error::msg("解密失败, 密码或密钥文件不正确"))?;
+    Ok(String::from_utf8(decrypted)?)
+}
+
+/// 密码或密钥文件: 环境变量 > 加密时使用的密钥文件 > 输入密码
+async fn crypto_secret(db: &DatabaseConnection) -> Result<Vec<u8>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase.into_bytes());
+    }
+    if let Ok(key_file) = std::env::var(KEY_FILE_ENV) {
+        return Ok(std::fs::read(key_file)?);
+    }
+    let key_file = load_property_from_db(db, CRYPTO_KEY_FILE_KEY.to_owned()).await?;
+    if !key_file.is_empty() {
+        return Ok(std::fs::read(key_file)?);
+    }
+    Ok(Password::new()
+        .with_prompt("请输入密码")
+        .interact()?
+        .into_bytes())
+}
+
+/// 取得解密用的密钥, 同一次运行只需要输入一次密码
+async fn crypto_key(db: &DatabaseConnection) -> Result<[u8; 32]> {
+    if let Some(key) = CRYPTO_KEY.get() {
+        return Ok(*key);
+    }
+    let salt = load_property_from_db(db, CRYPTO_SALT_KEY.to_owned()).await?;
+    if salt.is_empty() {
+        return Err(anyhow::Error::msg("登录信息没有加密"));
+    }
+    let key = derive_key(&crypto_secret(db).await?, &base64::decode(salt)?);
+    let check = load_property_from_db(db, CRYPTO_CHECK_KEY.to_owned()).await?;
+    if decrypt(&key, check.trim_start_matches(ENCRYPTED_PREFIX))? != CRYPTO_CHECK_VALUE {
+        return Err(anyhow::Error::msg("密码或密钥文件不正确"));
+    }
+    let _ = CRYPTO_KEY.set(key);
+    Ok(key)
+}
+
+/// 加密保存的登录信息
+pub(crate) async fn lock_properties(key_file: Option<String>) -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let db = db.deref();
+    if is_locked(db).await? {
+        return Err(anyhow::Error::msg("登录信息已经加密"));
+    }
+    // 在写入之前检查全部输入, 失败时不修改数据库
+    let key_file = match key_file {
+        Some(key_file) => Some(
+            std::fs::canonicalize(key_file)?
+                .to_str()
+                .unwrap()
+                .to_owned(),
+        ),
+        None => None,
+    };
+    let secret = match &key_file {
+        Some(key_file) => std::fs::read(key_file)?,
+        None => match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => passphrase.into_bytes(),
+            Err(_) => Password::new()
+                .with_prompt("设置密码")
+                .with_confirmation("确认密码", "两次输入的密码不一致")
+                .interact()?
+                .into_bytes(),
+        },
+    };
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key = derive_key(&secret, &salt);
+    let mut values = vec![
+        (CRYPTO_SALT_KEY.to_owned(), base64::encode(salt)),
+        (
+            CRYPTO_CHECK_KEY.to_owned(),
+            encrypt(&key, CRYPTO_CHECK_VALUE)?,
+        ),
+    ];
+    if let Some(key_file) = key_file {
+        values.push((CRYPTO_KEY_FILE_KEY.to_owned(), key_file));
+    }
+    for in_db in property::Entity::find().all(db).await? {
+        if is_sensitive_key(in_db.k.as_str()) && !in_db.v.starts_with(ENCRYPTED_PREFIX) {
+            let v = encrypt(&key, in_db.v.as_str())?;
+            values.push((in_db.k, v));
+        }
+    }
+    // 全部加密后在一个事务中写入, 不会只加密一部分
+    let txn = db.begin().await?;
+    for (k, v) in values {
+        save_property_from_db(&txn, k, v).await?;
+    }
+    txn.commit().await?;
+    let _ = CRYPTO_KEY.set(key);
+    Ok(())
+}
+
+/// 解密保存的登录信息
+pub(crate) async fn unlock_properties() -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let db = db.deref();
+    if !is_locked(db).await? {
+        return Err(anyhow::Error::msg("登录信息没有加密"));
+    }
+    let key = crypto_key(db).await?;
+    let mut values = vec![];
+    for in_db in property::Entity::find().all(db).await? {
+        if let Some(encrypted) = in_db.v.strip_prefix(ENCRYPTED_PREFIX) {
+            if is_sensitive_key(in_db.k.as_str()) {
+                values.push((in_db.k.clone(), decrypt(&key, encrypted)?));
+            }
+        }
+    }
+    let txn = db.begin().await?;
+    for (k, v) in values {
+        save_property_from_db(&txn, k, v).await?;
+    }
+    delete_property_from_db(&txn, CRYPTO_CHECK_KEY.to_owned()).await?;
+    delete_property_from_db(&txn, CRYPTO_KEY_FILE_KEY.to_owned()).await?;
+    delete_property_from_db(&txn, CRYPTO_SALT_KEY.to_owned()).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// 从数据库读取以prefix开头的全部配置
+pub(crate) async fn load_properties_with_prefix_from_db(
+    db: &DatabaseConnection,
+    prefix: &str,
+) -> Result<Vec<(String, String)>> {
+    Ok(property::Entity::find()
+        .filter(property::Column::K.starts_with(prefix))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|in_db| (in_db.k, in_db.v))
+        .collect())
+}
+
+/// 从默认数据库读取以prefix开头的全部配置
+pub(crate) async fn load_properties_with_prefix(prefix: &str) -> Result<Vec<(String, String)>> {
+    load_properties_with_prefix_from_db(PROPERTY_DB.get().await.lock().await.deref(), prefix).await
+}
+
+/// 从数据库删除配置
+pub(crate) async fn delete_property_from_db<C: ConnectionTrait>(db: &C, k: String) -> Result<()> {
+    property::Entity::delete_by_id(k).exec(db).await?;
+    Ok(())
+}
+
+/// 从默认数据库删除配置
+pub(crate) async fn delete_property(k: String) -> Result<()> {
+    delete_property_from_db(PROPERTY_DB.get().await.lock().await.deref(), k).await
+}
+
+/// 在一个事务中从默认数据库删除多个配置
+pub(crate) async fn delete_properties(keys: Vec<String>) -> Result<()> {
+    let db = PROPERTY_DB.get().await.lock().await;
+    let txn = db.deref().begin().await?;
+    for k in keys {
+        delete_property_from_db(&txn, k).await?;
+    }
+    txn.commit().await?;
+    Ok(())
 }
 
 pub(crate) fn allowed_file_name(title: &str) -> String {
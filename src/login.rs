@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use image::Luma;
+use qrcode::QrCode;
+use tokio::time::{sleep, Instant};
+
+use crate::api::{LoginQrPoll, WebApi};
+use crate::local::{join_paths, template_dir};
+use crate::{app, profile, token, web_api_with_token};
+
+/// 轮询扫码结果的间隔
+const QR_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// 扫码登录的状态
+#[derive(Clone, Copy, PartialEq)]
+enum QrLoginState {
+    /// 未扫描
+    Waiting,
+    /// 已扫描, 等待在手机上确认
+    Scanned,
+    /// 二维码已过期
+    Expired,
+    /// 已确认
+    Confirmed,
+}
+
+impl QrLoginState {
+    fn from_poll(poll: &LoginQrPoll) -> crate::Result<Self> {
+        // 86038：二维码已失效
+        // 86090：已扫码未确认
+        // 86101：未扫码
+        match poll.code {
+            0 => Ok(Self::Confirmed),
+            86038 => Ok(Self::Expired),
+            86101 => Ok(Self::Waiting),
+            86090 => Ok(Self::Scanned),
+            other => Err(anyhow::Error::msg(format!(
+                "登录失败 : 未知的状态 {} : {}",
+                other, poll.message
+            ))),
+        }
+    }
+}
+
+/// 保存在临时文件夹中的二维码图片, 登录结束后删除
+struct QrImage(String);
+
+impl Drop for QrImage {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+pub(crate) async fn login() -> crate::Result<()> {
+    if let Some(path) = app::cookies_value() {
+        let content = std::fs::read_to_string(path.as_str())?;
+        let cookies = token::parse_cookies(content.as_str());
+        return login_with_cookies(cookies).await;
+    }
+    if let Some(sess_data) = app::sess_data_value() {
+        let mut cookies = HashMap::new();
+        cookies.insert("SESSDATA".to_owned(), sess_data);
+        return login_with_cookies(cookies).await;
+    }
+    login_qr().await
+}
+
+/// 使用浏览器中导出的cookie登录
+async fn login_with_cookies(cookies: HashMap<String, String>) -> crate::Result<()> {
+    let web_token = token::web_token_from_cookies(&cookies)?;
+    match web_api_with_token(&web_token).nav().await {
+        Ok(nav) if nav.is_login => {}
+        Ok(_) => return Err(anyhow::Error::msg("cookie无效, 请确认已经在浏览器中登录")),
+        Err(err) => {
+            return Err(anyhow::Error::msg(format!(
+                "cookie无效, 请确认已经在浏览器中登录 : {}",
+                err
+            )))
+        }
+    }
+    let meta = token::TokenMeta {
+        bili_jct: cookies.get("bili_jct").cloned().unwrap_or_default(),
+        refresh_token: app::refresh_token_value().unwrap_or_default(),
+    };
+    let profile = profile::active_profile().await?;
+    token::save_web_token(profile.as_str(), &web_token).await?;
+    token::save_token_meta(profile.as_str(), &meta).await?;
+    println!("OK : {}", profile);
+    Ok(())
+}
+
+/// 使用二维码登录, 二维码过期时重新生成, 超时后返回错误
+pub(crate) async fn login_qr() -> crate::Result<()> {
+    let web_api = WebApi::new();
+    let deadline = Instant::now() + Duration::from_secs(app::login_timeout_value());
+    loop {
+        let qr = web_api.login_qr().await?;
+        let _qr_image = show_qr(qr.url.as_str())?;
+        println!("请使用哔哩哔哩客户端扫描二维码");
+        let mut last_state = QrLoginState::Waiting;
+        loop {
+            if Instant::now() >= deadline {
+                return Err(anyhow::Error::msg("登录超时"));
+            }
+            sleep(QR_POLL_INTERVAL).await;
+            let poll = web_api.login_qr_poll(qr.qrcode_key.as_str()).await?;
+            let state = QrLoginState::from_poll(&poll)?;
+            match state {
+                QrLoginState::Waiting => {}
+                QrLoginState::Scanned => {
+                    if last_state != QrLoginState::Scanned {
+                        println!("已扫描, 请在手机上确认登录");
+                    }
+                }
+                QrLoginState::Expired => {
+                    println!("二维码已过期, 重新生成");
+                    break;
+                }
+                QrLoginState::Confirmed => {
+                    // url中的cookie和旧的扫码接口格式一致
+                    let web_token =
+                        bilirust::Client::new().login_qr_info_parse_token(poll.url.clone())?;
+                    let profile = profile::active_profile().await?;
+                    token::save_web_token(profile.as_str(), &web_token).await?;
+                    let params = token::query_params(poll.url.as_str());
+                    let meta = token::TokenMeta {
+                        bili_jct: params.get("bili_jct").cloned().unwrap_or_default(),
+                        refresh_token: poll.refresh_token,
+                    };
+                    token::save_token_meta(profile.as_str(), &meta).await?;
+                    println!("OK : {}", profile);
+                    return Ok(());
+                }
+            }
+            last_state = state;
+        }
+    }
+}
+
+/// 显示二维码, 保存为图片时返回图片, 打不开图片时在控制台输出
+fn show_qr(url: &str) -> crate::Result<Option<QrImage>> {
+    if app::qr_console_value() {
+        print_qr(url)?;
+        return Ok(None);
+    }
+    let code = QrCode::new(url.as_bytes())?;
+    let image = code.render::<Luma<u8>>().build();
+    let path = join_paths(vec![
+        &template_dir(),
+        &(uuid::Uuid::new_v4().to_string() + ".png"),
+    ]);
+    image.save(path.as_str())?;
+    let qr_image = QrImage(path);
+    if let Err(err) = opener::open(qr_image.0.as_str()) {
+        println!("未能打开二维码图片 : {}", err);
+        print_qr(url)?;
+    }
+    Ok(Some(qr_image))
+}
+
+/// 在控制台输出二维码
+fn print_qr(url: &str) -> crate::Result<()> {
+    qr2term::print_qr(url).map_err(|err| anyhow::Error::msg(format!("{:?}", err)))
+}
@@ -1,102 +1,263 @@
 use crate::app::init_app;
 pub(crate) use anyhow::Result;
 use bilirust::WebToken;
-use image::Luma;
-use local::{join_paths, load_property, save_property, template_dir};
-use qrcode::QrCode;
-use serde_json::{from_str, to_string};
+use chrono::{Local, TimeZone};
+use dialoguer::Confirm;
+use local::load_property;
+use serde_json::from_str;
+use std::io::IsTerminal;
 use std::process::exit;
-use std::thread::sleep;
-use std::time::Duration;
 
+mod api;
 mod app;
+mod bangumi;
+mod chapter;
+mod config;
+mod cover;
+mod danmaku;
 mod down;
 mod entities;
 mod ffmpeg;
+mod http;
 mod local;
+mod login;
+mod mp4;
+mod mux;
+mod play;
+mod profile;
+mod region;
+mod search;
+mod serve;
+mod sync;
+mod token;
+mod transcode;
+mod tui;
+mod watch;
 
 #[tokio::main]
 async fn main() {
     init_app();
-    run_app().await.unwrap();
+    if let Err(err) = run_app().await {
+        println!("{}", err);
+        exit(1);
+    }
 }
 
 async fn run_app() -> crate::Result<()> {
-    ffmpeg::ffmpeg_run_version()?;
-    match app::subcommand() {
+    config::init_config().await?;
+    http::init_http()?;
+    let subcommand = app::subcommand();
+    // 转码和封面的配置只在下载和合并时检查, 配置错误时仍然可以使用config修改
+    if matches!(
+        subcommand.as_deref(),
+        Some("down" | "search" | "sync" | "bangumi" | "watch" | "merge" | "tui")
+    ) {
+        transcode::init_transcode()?;
+        cover::init_cover()?;
+    }
+    match subcommand {
         None => app::print_help()?,
         Some(subcommand) => match subcommand.as_str() {
-            "login" => login().await?,
+            "login" => login::login().await?,
             "user" => user().await?,
+            "profile" => profile::profile().await?,
+            "config" => config::config().await?,
             "down" => down::down().await?,
+            "search" => search::search().await?,
+            "sync" => sync::sync().await?,
+            "bangumi" => bangumi::bangumi().await?,
+            "watch" => watch::watch().await?,
+            "merge" => mux::merge().await?,
+            "tui" => tui::tui().await?,
+            "play" => play::play().await?,
+            "serve" => serve::serve().await?,
             _ => app::print_help()?,
         },
     }
     Ok(())
 }
 
-async fn login() -> Result<()> {
-    let client = bilirust::Client::new();
-    let qr_data = client.login_qr().await.unwrap();
-    if app::qr_console_value() {
-        qr2term::print_qr(qr_data.url.clone().as_str()).unwrap();
-    } else {
-        let code = QrCode::new(qr_data.url.clone().as_str().as_bytes()).unwrap();
-        let image = code.render::<Luma<u8>>().build();
-        let path = join_paths(vec![
-            &template_dir(),
-            &(uuid::Uuid::new_v4().to_string() + ".png"),
-        ]);
-        image.save(path.as_str()).unwrap();
-        opener::open(path).unwrap();
+/// 读取保存的登录信息, 未登录时返回None
+async fn load_web_token() -> Result<Option<WebToken>> {
+    let profile = profile::active_profile().await?;
+    let property = load_property(profile::web_token_key(profile.as_str())).await?;
+    if &property == "" {
+        return Ok(None);
     }
+    Ok(Some(from_str(property.as_str())?))
+}
 
-    loop {
-        sleep(Duration::new(3, 0));
-        let info = client.login_qr_info(qr_data.oauth_key.clone()).await;
-        match info {
-            Ok(info) => {
-                // -1：密钥错误
-                // -2：密钥超时
-                // -4：未扫描
-                // -5：未确认
-                match info.error_data {
-                    0 => {
-                        let web_token = client
-                            .login_qr_info_parse_token(info.url.to_string())
-                            .unwrap();
-                        let web_token_string = to_string(&web_token).unwrap();
-                        save_property("web_token".to_owned(), web_token_string).await?;
-                        println!("OK");
-                        break;
-                    }
-                    -4 => continue,
-                    -5 => continue,
-                    -2 => panic!("time out"),
-                    other => panic!("ERROR : {}", other),
-                }
-            }
-            Err(err) => {
-                panic!("{}", err);
-            }
+async fn login_client() -> Result<api::WebApi> {
+    // 终端界面中下载时不能提示重新登录
+    checked_login_client(std::io::stdin().is_terminal() && !down::in_tui()).await
+}
+
+/// 检查登录状态后创建客户端, interactive为false时登录失效不提示重新登录, 直接返回错误
+async fn checked_login_client(interactive: bool) -> Result<api::WebApi> {
+    let profile = profile::active_profile().await?;
+    let token = match load_web_token().await? {
+        Some(token) => token,
+        // 终端界面和后台任务中不能退出进程
+        None if !interactive => return Err(anyhow::Error::msg(format!("需要登录 : {}", profile))),
+        None => {
+            println!("需要登录 : {}", profile);
+            exit(1);
         }
-    }
-    Ok(())
+    };
+    let token = check_web_token(profile.as_str(), token, interactive).await?;
+    Ok(web_api_with_token(&token))
 }
 
-async fn login_client() -> Result<bilirust::Client> {
-    let property = load_property("web_token".to_owned()).await?;
-    if &property == "" {
-        println!("需要登录");
-        exit(1);
+/// 使用登录信息的客户端
+pub(crate) fn web_api_with_token(token: &WebToken) -> api::WebApi {
+    let mut web_api = api::WebApi::new();
+    web_api.set_sess_data(token.sessdata.clone());
+    web_api
+}
+
+/// 检查登录状态, 即将过期或失效时尝试刷新, 无法刷新时提示重新登录
+async fn check_web_token(profile: &str, token: WebToken, interactive: bool) -> Result<WebToken> {
+    let meta = token::load_token_meta(profile).await?;
+    let state = match token::token_state(&token, &meta).await {
+        Ok(state) => state,
+        Err(err) => {
+            println!("未能检查登录状态 : {}", err);
+            return Ok(token);
+        }
+    };
+    if state.is_login && !state.need_refresh {
+        return Ok(token);
     }
-    let token: WebToken = from_str(property.as_str())?;
-    let mut client = bilirust::Client::new();
-    client.login_set_sess_data(token.sessdata);
-    Ok(client)
+    match token::refresh_token(&token, &meta).await {
+        Ok((new_token, new_meta)) => {
+            token::save_web_token(profile, &new_token).await?;
+            token::save_token_meta(profile, &new_meta).await?;
+            println!("登录信息已刷新");
+            return Ok(new_token);
+        }
+        Err(err) => println!("未能刷新登录信息 : {}", err),
+    }
+    if state.is_login {
+        println!(
+            "登录信息即将过期 : {}, 请使用 bili-cli login 重新登录",
+            state.expires_description()
+        );
+        return Ok(token);
+    }
+    if !interactive {
+        return Err(anyhow::Error::msg(format!(
+            "登录信息已失效, 请使用 bili-cli --profile {} login 重新登录",
+            profile
+        )));
+    }
+    println!("登录信息已失效, 下载的视频清晰度会受到限制");
+    if Confirm::new()
+        .with_prompt("是否重新扫码登录")
+        .default(true)
+        .interact()?
+    {
+        login::login_qr().await?;
+        if let Some(token) = load_web_token().await? {
+            return Ok(token);
+        }
+    }
+    Ok(token)
+}
+
+/// 登录不是必须的接口, 不检查登录状态
+async fn web_api() -> Result<api::WebApi> {
+    Ok(match load_web_token().await? {
+        Some(token) => web_api_with_token(&token),
+        None => api::WebApi::new(),
+    })
 }
 
+/// 清晰度以及需要的账号权限: (名称, 是否需要大会员)
+const QUALITY_ENTITLEMENTS: [(&str, bool); 9] = [
+    ("720P", false),
+    ("1080P", false),
+    ("1080P+ 高码率", true),
+    ("1080P60 高帧率", true),
+    ("4K 超高清", true),
+    ("HDR 真彩", true),
+    ("杜比视界", true),
+    ("杜比全景声", true),
+    ("8K 超高清", true),
+];
+
 async fn user() -> Result<()> {
-    println!("{:?}", login_client().await?.my_info().await?);
+    let profile = profile::active_profile().await?;
+    let token = match load_web_token().await? {
+        Some(token) => token,
+        None => {
+            println!("需要登录 : {}", profile);
+            exit(1);
+        }
+    };
+    let meta = token::load_token_meta(profile.as_str()).await?;
+    let state = token::token_state(&token, &meta).await?;
+    println!("账号 : {}", profile);
+    println!(
+        "登录状态 : {}",
+        if state.is_login {
+            "有效"
+        } else {
+            "已失效"
+        }
+    );
+    println!("过期时间 : {}", state.expires_description());
+    println!(
+        "自动刷新 : {}",
+        if meta.refresh_token.is_empty() {
+            "不可用"
+        } else if state.need_refresh {
+            "需要刷新"
+        } else {
+            "不需要刷新"
+        }
+    );
+    if !state.is_login {
+        println!();
+        println!("登录信息已失效, 只能下载480P及以下的清晰度, 请重新登录");
+        return Ok(());
+    }
+    let nav = web_api_with_token(&token).nav().await?;
+    println!();
+    println!("用户名 : {}", nav.uname);
+    println!("UID : {}", nav.mid);
+    println!("等级 : LV{}", nav.level_info.current_level);
+    println!("硬币 : {}", nav.money);
+    if nav.is_vip() {
+        let label = if nav.vip_label.text.is_empty() {
+            if nav.vip_type == 2 {
+                "年度大会员"
+            } else {
+                "大会员"
+            }
+        } else {
+            nav.vip_label.text.as_str()
+        };
+        let due_date = Local
+            .timestamp_millis_opt(nav.vip_due_date)
+            .single()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        println!("会员 : {} (到期时间 {})", label, due_date);
+    } else {
+        println!("会员 : 无");
+    }
+    println!();
+    println!("可以下载的清晰度 :");
+    for (name, need_vip) in QUALITY_ENTITLEMENTS {
+        let allowed = !need_vip || nav.is_vip();
+        println!(
+            "  [{}] {}{}",
+            if allowed { "✓" } else { "✗" },
+            name,
+            if need_vip { " (需要大会员)" } else { "" }
+        );
+    }
+    println!();
+    println!("下载时可选择的清晰度以视频本身提供的为准");
     Ok(())
 }
@@ -0,0 +1,1018 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+
+use anyhow::Context;
+
+use crate::chapter::Chapter;
+use crate::mux::Muxer;
+
+/// 不依赖ffmpeg, 将DASH的fMP4音频和视频重新封装为普通的MP4
+pub(crate) struct NativeMuxer;
+
+impl Muxer for NativeMuxer {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn merge(
+        &self,
+        video: &str,
+        audio: &str,
+        chapters: &[Chapter],
+        output: &str,
+    ) -> crate::Result<()> {
+        let video = read_track(video).with_context(|| format!("未能读取视频 : {}", video))?;
+        let audio = read_track(audio).with_context(|| format!("未能读取音频 : {}", audio))?;
+        write_mp4(&[video, audio], chapters, output)
+    }
+}
+
+/// 一个sample
+struct Sample {
+    duration: u32,
+    size: u32,
+    flags: u32,
+    composition_offset: i32,
+}
+
+/// 输入文件中连续存放的一组sample
+struct Chunk {
+    /// 在输入文件中的位置
+    offset: u64,
+    size: u64,
+    sample_count: u32,
+    /// 第一个sample的解码时间
+    decode_time: u64,
+}
+
+/// 从fMP4读取的轨道
+struct Track {
+    file: String,
+    tkhd: Vec<u8>,
+    mdhd: Vec<u8>,
+    hdlr: Vec<u8>,
+    /// vmhd或者smhd
+    media_header: Option<Vec<u8>>,
+    dinf: Option<Vec<u8>>,
+    stsd: Vec<u8>,
+    timescale: u32,
+    samples: Vec<Sample>,
+    chunks: Vec<Chunk>,
+}
+
+impl Track {
+    fn duration(&self) -> u64 {
+        self.samples.iter().map(|x| x.duration as u64).sum()
+    }
+
+    /// 按照movie的时间单位(毫秒)计算的时长
+    fn movie_duration(&self) -> u64 {
+        self.duration() * MOVIE_TIMESCALE as u64 / self.timescale.max(1) as u64
+    }
+
+    /// 第一帧的显示时间, 有B帧时大于0, 需要使用编辑列表从这里开始播放
+    fn presentation_start(&self) -> i64 {
+        let mut decode_time = 0i64;
+        let mut start = i64::MAX;
+        for sample in &self.samples {
+            start = start.min(decode_time + sample.composition_offset as i64);
+            decode_time += sample.duration as i64;
+        }
+        start.max(0)
+    }
+}
+
+/// trex中的默认值
+#[derive(Default, Clone, Copy)]
+struct SampleDefaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+const MOVIE_TIMESCALE: u32 = 1000;
+
+/// sample_is_non_sync_sample
+const NON_SYNC_SAMPLE: u32 = 0x10000;
+
+/// box的类型, 完整的box, box的内容
+type BoxRef<'a> = ([u8; 4], &'a [u8], &'a [u8]);
+
+/// 读取 (box的大小, 类型, 头部的大小), 到文件结尾时返回None
+fn read_box_header(file: &mut File, file_len: u64) -> crate::Result<Option<(u64, [u8; 4], u64)>> {
+    let position = file.stream_position()?;
+    if position + 8 > file_len {
+        return Ok(None);
+    }
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+    let size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+    let box_type: [u8; 4] = header[4..8].try_into().unwrap();
+    let (size, header_size) = match size {
+        0 => (file_len - position, 8),
+        1 => {
+            let mut large = [0u8; 8];
+            file.read_exact(&mut large)?;
+            (u64::from_be_bytes(large), 16)
+        }
+        size => (size, 8),
+    };
+    if size < header_size || position + size > file_len {
+        return Err(anyhow::Error::msg(format!(
+            "MP4文件已损坏 : {} 的大小错误",
+            String::from_utf8_lossy(&box_type)
+        )));
+    }
+    Ok(Some((size, box_type, header_size)))
+}
+
+/// 读取内存中的子box
+fn children(data: &[u8]) -> crate::Result<Vec<BoxRef<'_>>> {
+    let mut boxes = vec![];
+    let mut position = 0;
+    while position + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[position..position + 4].try_into().unwrap()) as usize;
+        let box_type: [u8; 4] = data[position + 4..position + 8].try_into().unwrap();
+        let (size, header_size) = match size {
+            0 => (data.len() - position, 8),
+            1 if position + 16 <= data.len() => (
+                u64::from_be_bytes(data[position + 8..position + 16].try_into().unwrap()) as usize,
+                16,
+            ),
+            size => (size, 8),
+        };
+        if size < header_size || position + size > data.len() {
+            return Err(anyhow::Error::msg(format!(
+                "MP4文件已损坏 : {} 的大小错误",
+                String::from_utf8_lossy(&box_type)
+            )));
+        }
+        boxes.push((
+            box_type,
+            &data[position..position + size],
+            &data[position + header_size..position + size],
+        ));
+        position += size;
+    }
+    Ok(boxes)
+}
+
+fn find_child<'a>(data: &'a [u8], box_type: &[u8; 4]) -> crate::Result<Option<BoxRef<'a>>> {
+    Ok(children(data)?.into_iter().find(|x| &x.0 == box_type))
+}
+
+fn require_child<'a>(data: &'a [u8], box_type: &[u8; 4]) -> crate::Result<BoxRef<'a>> {
+    find_child(data, box_type)?.with_context(|| {
+        format!(
+            "不支持的MP4文件 : 没有找到 {}",
+            String::from_utf8_lossy(box_type)
+        )
+    })
+}
+
+/// 按照大端读取数字
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> crate::Result<&'a [u8]> {
+        if self.position + len > self.data.len() {
+            return Err(anyhow::Error::msg("MP4文件已损坏 : box的内容不完整"));
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> crate::Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> crate::Result<u64> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+/// 读取fMP4中唯一的轨道和全部的sample
+fn read_track(path: &str) -> crate::Result<Track> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut track: Option<Track> = None;
+    let mut defaults = SampleDefaults::default();
+    let mut track_id = 0;
+    let mut next_decode_time = 0;
+    while let Some((size, box_type, header_size)) = read_box_header(&mut file, file_len)? {
+        let start = file.stream_position()? - header_size;
+        match &box_type {
+            b"moov" | b"moof" => {
+                let mut data = vec![0u8; (size - header_size) as usize];
+                file.read_exact(&mut data)?;
+                if &box_type == b"moov" {
+                    let (moov_track, moov_track_id, moov_defaults) = read_moov(path, &data)?;
+                    track = Some(moov_track);
+                    track_id = moov_track_id;
+                    defaults = moov_defaults;
+                } else {
+                    let track = track
+                        .as_mut()
+                        .with_context(|| "不支持的MP4文件 : moof在moov之前")?;
+                    read_moof(
+                        track,
+                        track_id,
+                        defaults,
+                        &data,
+                        start,
+                        &mut next_decode_time,
+                    )?;
+                }
+            }
+            _ => {
+                file.seek(SeekFrom::Start(start + size))?;
+            }
+        }
+    }
+    let track = track.with_context(|| "不支持的MP4文件 : 没有找到moov")?;
+    if track.samples.is_empty() {
+        return Err(anyhow::Error::msg(
+            "不支持的MP4文件 : 没有找到fMP4的分段(moof), 只能合并DASH的音频和视频",
+        ));
+    }
+    Ok(track)
+}
+
+/// 读取MP4文件的时长(秒)
+pub(crate) fn duration(path: &str) -> crate::Result<f64> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    while let Some((size, box_type, header_size)) = read_box_header(&mut file, file_len)? {
+        let start = file.stream_position()? - header_size;
+        if &box_type == b"moov" {
+            let mut data = vec![0u8; (size - header_size) as usize];
+            file.read_exact(&mut data)?;
+            let mvhd = require_child(&data, b"mvhd")?;
+            let mut reader = Reader::new(mvhd.2);
+            let version = reader.u32()? >> 24;
+            let (timescale, duration) = if version == 1 {
+                reader.bytes(16)?;
+                (reader.u32()?, reader.u64()?)
+            } else {
+                reader.bytes(8)?;
+                (reader.u32()?, reader.u32()? as u64)
+            };
+            if timescale == 0 {
+                return Err(anyhow::Error::msg("MP4文件已损坏 : mvhd的timescale为0"));
+            }
+            return Ok(duration as f64 / timescale as f64);
+        }
+        file.seek(SeekFrom::Start(start + size))?;
+    }
+    Err(anyhow::Error::msg("不支持的MP4文件 : 没有找到moov"))
+}
+
+fn read_moov(path: &str, moov: &[u8]) -> crate::Result<(Track, u32, SampleDefaults)> {
+    let traks = children(moov)?
+        .into_iter()
+        .filter(|x| &x.0 == b"trak")
+        .collect::<Vec<_>>();
+    if traks.len() != 1 {
+        return Err(anyhow::Error::msg(format!(
+            "不支持的MP4文件 : 只能包含一个轨道, 实际为 {} 个",
+            traks.len()
+        )));
+    }
+    let trak = traks[0].2;
+    let tkhd = require_child(trak, b"tkhd")?;
+    let mdia = require_child(trak, b"mdia")?;
+    let mdhd = require_child(mdia.2, b"mdhd")?;
+    let hdlr = require_child(mdia.2, b"hdlr")?;
+    let minf = require_child(mdia.2, b"minf")?;
+    let stbl = require_child(minf.2, b"stbl")?;
+    let stsd = require_child(stbl.2, b"stsd")?;
+    let media_header = match find_child(minf.2, b"vmhd")? {
+        Some(vmhd) => Some(vmhd),
+        None => find_child(minf.2, b"smhd")?,
+    };
+    let dinf = find_child(minf.2, b"dinf")?;
+    // tkhd的track_ID
+    let mut reader = Reader::new(tkhd.2);
+    let version = reader.bytes(4)?[0];
+    reader.bytes(if version == 1 { 16 } else { 8 })?;
+    let track_id = reader.u32()?;
+    // mdhd的timescale
+    let mut reader = Reader::new(mdhd.2);
+    let version = reader.bytes(4)?[0];
+    reader.bytes(if version == 1 { 16 } else { 8 })?;
+    let timescale = reader.u32()?;
+    // mvex中的默认值
+    let mut defaults = SampleDefaults::default();
+    if let Some(mvex) = find_child(moov, b"mvex")? {
+        for (box_type, _, trex) in children(mvex.2)? {
+            if &box_type != b"trex" {
+                continue;
+            }
+            let mut reader = Reader::new(trex);
+            reader.bytes(4)?;
+            if reader.u32()? != track_id {
+                continue;
+            }
+            reader.u32()?;
+            defaults = SampleDefaults {
+                duration: reader.u32()?,
+                size: reader.u32()?,
+                flags: reader.u32()?,
+            };
+        }
+    }
+    let track = Track {
+        file: path.to_owned(),
+        tkhd: tkhd.1.to_vec(),
+        mdhd: mdhd.1.to_vec(),
+        hdlr: hdlr.1.to_vec(),
+        media_header: media_header.map(|x| x.1.to_vec()),
+        dinf: dinf.map(|x| x.1.to_vec()),
+        stsd: stsd.1.to_vec(),
+        timescale,
+        samples: vec![],
+        chunks: vec![],
+    };
+    Ok((track, track_id, defaults))
+}
+
+/// 读取一个moof中的sample, moof_start为moof在文件中的位置
+fn read_moof(
+    track: &mut Track,
+    track_id: u32,
+    defaults: SampleDefaults,
+    moof: &[u8],
+    moof_start: u64,
+    next_decode_time: &mut u64,
+) -> crate::Result<()> {
+    for (box_type, _, traf) in children(moof)? {
+        if &box_type != b"traf" {
+            continue;
+        }
+        // tfhd
+        let tfhd = require_child(traf, b"tfhd")?;
+        let mut reader = Reader::new(tfhd.2);
+        let flags = reader.u32()? & 0xFFFFFF;
+        if reader.u32()? != track_id {
+            continue;
+        }
+        let base_offset = if flags & 0x1 != 0 {
+            reader.u64()?
+        } else {
+            moof_start
+        };
+        if flags & 0x2 != 0 {
+            reader.u32()?;
+        }
+        let mut traf_defaults = defaults;
+        if flags & 0x8 != 0 {
+            traf_defaults.duration = reader.u32()?;
+        }
+        if flags & 0x10 != 0 {
+            traf_defaults.size = reader.u32()?;
+        }
+        if flags & 0x20 != 0 {
+            traf_defaults.flags = reader.u32()?;
+        }
+        // tfdt
+        if let Some(tfdt) = find_child(traf, b"tfdt")? {
+            let mut reader = Reader::new(tfdt.2);
+            let version = reader.bytes(4)?[0];
+            *next_decode_time = if version == 1 {
+                reader.u64()?
+            } else {
+                reader.u32()? as u64
+            };
+        }
+        // trun
+        let mut data_position = base_offset;
+        for (box_type, _, trun) in children(traf)? {
+            if &box_type != b"trun" {
+                continue;
+            }
+            let mut reader = Reader::new(trun);
+            let version_flags = reader.u32()?;
+            let flags = version_flags & 0xFFFFFF;
+            let sample_count = reader.u32()?;
+            if flags & 0x1 != 0 {
+                data_position = (base_offset as i64 + reader.u32()? as i32 as i64) as u64;
+            }
+            let first_sample_flags = if flags & 0x4 != 0 {
+                Some(reader.u32()?)
+            } else {
+                None
+            };
+            let mut chunk = Chunk {
+                offset: data_position,
+                size: 0,
+                sample_count,
+                decode_time: *next_decode_time,
+            };
+            for i in 0..sample_count {
+                let duration = if flags & 0x100 != 0 {
+                    reader.u32()?
+                } else {
+                    traf_defaults.duration
+                };
+                let size = if flags & 0x200 != 0 {
+                    reader.u32()?
+                } else {
+                    traf_defaults.size
+                };
+                let sample_flags = if flags & 0x400 != 0 {
+                    reader.u32()?
+                } else if i == 0 && first_sample_flags.is_some() {
+                    first_sample_flags.unwrap()
+                } else {
+                    traf_defaults.flags
+                };
+                // version 0 时为无符号数, 实际上不会超过i32
+                let composition_offset = if flags & 0x800 != 0 {
+                    reader.u32()? as i32
+                } else {
+                    0
+                };
+                track.samples.push(Sample {
+                    duration,
+                    size,
+                    flags: sample_flags,
+                    composition_offset,
+                });
+                chunk.size += size as u64;
+                *next_decode_time += duration as u64;
+            }
+            data_position += chunk.size;
+            if sample_count > 0 {
+                track.chunks.push(chunk);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 写入box, 内容由content写入
+fn write_box(out: &mut Vec<u8>, box_type: &[u8; 4], content: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(box_type);
+    content(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+/// 写入full box (带有version和flags)
+fn write_full_box(
+    out: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    content: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, box_type, |out| {
+        out.extend_from_slice(&((version as u32) << 24 | (flags & 0xFFFFFF)).to_be_bytes());
+        content(out);
+    })
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// 按照解码时间交错写入全部轨道的chunk, 返回每个轨道每个chunk在mdat中的位置
+fn chunk_order(tracks: &[Track]) -> Vec<(usize, usize)> {
+    let mut order = vec![];
+    for (track_index, track) in tracks.iter().enumerate() {
+        for chunk_index in 0..track.chunks.len() {
+            order.push((track_index, chunk_index));
+        }
+    }
+    order.sort_by(|a, b| {
+        let a_track = &tracks[a.0];
+        let b_track = &tracks[b.0];
+        // 比较 a_time / a_timescale 和 b_time / b_timescale
+        let a_time = a_track.chunks[a.1].decode_time as u128 * b_track.timescale as u128;
+        let b_time = b_track.chunks[b.1].decode_time as u128 * a_track.timescale as u128;
+        a_time.cmp(&b_time).then(a.0.cmp(&b.0))
+    });
+    order
+}
+
+/// 写入MP4, moov放在mdat之前 (faststart), 不需要下载完整个文件就可以开始播放
+fn write_mp4(tracks: &[Track], chapters: &[Chapter], output: &str) -> crate::Result<()> {
+    let order = chunk_order(tracks);
+    let data_size: u64 = tracks
+        .iter()
+        .flat_map(|track| track.chunks.iter())
+        .map(|chunk| chunk.size)
+        .sum();
+    let mut ftyp = vec![];
+    write_box(&mut ftyp, b"ftyp", |out| {
+        out.extend_from_slice(b"isom");
+        put_u32(out, 0x200);
+        out.extend_from_slice(b"isomiso2mp41");
+    });
+    // 大于4G时使用largesize
+    let mdat_header_size: u64 = if data_size + 8 > u32::MAX as u64 {
+        16
+    } else {
+        8
+    };
+    let moov = layout_moov(
+        tracks,
+        chapters,
+        &order,
+        ftyp.len() as u64 + mdat_header_size,
+    );
+    // 写入文件
+    let mut out = BufWriter::new(File::create(output)?);
+    out.write_all(&ftyp)?;
+    out.write_all(&moov)?;
+    if mdat_header_size == 16 {
+        out.write_all(&1u32.to_be_bytes())?;
+        out.write_all(b"mdat")?;
+        out.write_all(&(data_size + 16).to_be_bytes())?;
+    } else {
+        out.write_all(&((data_size + 8) as u32).to_be_bytes())?;
+        out.write_all(b"mdat")?;
+    }
+    let mut inputs = tracks
+        .iter()
+        .map(|track| File::open(&track.file))
+        .collect::<std::io::Result<Vec<File>>>()?;
+    for (track_index, chunk_index) in &order {
+        let chunk = &tracks[*track_index].chunks[*chunk_index];
+        let input = &mut inputs[*track_index];
+        input.seek(SeekFrom::Start(chunk.offset))?;
+        let copied = std::io::copy(&mut input.take(chunk.size), &mut out)?;
+        if copied != chunk.size {
+            return Err(anyhow::Error::msg(format!(
+                "MP4文件不完整 : {}",
+                tracks[*track_index].file
+            )));
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// chunk的位置取决于moov的大小, moov的大小又取决于使用stco还是co64, 重复计算直到大小不变
+/// header_size为moov之外mdat的数据之前的大小
+fn layout_moov(
+    tracks: &[Track],
+    chapters: &[Chapter],
+    order: &[(usize, usize)],
+    header_size: u64,
+) -> Vec<u8> {
+    let mut moov = vec![];
+    loop {
+        let mut chunk_offsets: Vec<Vec<u64>> = tracks
+            .iter()
+            .map(|track| vec![0; track.chunks.len()])
+            .collect();
+        let mut position = header_size + moov.len() as u64;
+        for (track_index, chunk_index) in order {
+            chunk_offsets[*track_index][*chunk_index] = position;
+            position += tracks[*track_index].chunks[*chunk_index].size;
+        }
+        let new_moov = build_moov(tracks, chapters, &chunk_offsets);
+        let stable = new_moov.len() == moov.len();
+        moov = new_moov;
+        if stable {
+            return moov;
+        }
+    }
+}
+
+fn build_moov(tracks: &[Track], chapters: &[Chapter], chunk_offsets: &[Vec<u64>]) -> Vec<u8> {
+    let duration = tracks.iter().map(|x| x.movie_duration()).max().unwrap_or(0);
+    let mut moov = vec![];
+    write_box(&mut moov, b"moov", |out| {
+        write_full_box(out, b"mvhd", 1, 0, |out| {
+            out.extend_from_slice(&[0; 16]);
+            put_u32(out, MOVIE_TIMESCALE);
+            out.extend_from_slice(&duration.to_be_bytes());
+            put_u32(out, 0x00010000);
+            out.extend_from_slice(&[0x01, 0x00]);
+            out.extend_from_slice(&[0; 10]);
+            for value in [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000] {
+                put_u32(out, value);
+            }
+            out.extend_from_slice(&[0; 24]);
+            put_u32(out, tracks.len() as u32 + 1);
+        });
+        for (index, track) in tracks.iter().enumerate() {
+            write_trak(out, track, index as u32 + 1, &chunk_offsets[index]);
+        }
+        if !chapters.is_empty() {
+            write_box(out, b"udta", |out| write_chpl(out, chapters));
+        }
+    });
+    moov
+}
+
+/// Nero格式的章节, ffmpeg / mpv / VLC 等都可以读取
+/// 最多255个章节, 标题最长255字节
+fn write_chpl(out: &mut Vec<u8>, chapters: &[Chapter]) {
+    write_full_box(out, b"chpl", 1, 0, |out| {
+        put_u32(out, 0);
+        let chapters = &chapters[..chapters.len().min(u8::MAX as usize)];
+        out.push(chapters.len() as u8);
+        for chapter in chapters {
+            // 单位为100纳秒
+            out.extend_from_slice(&(chapter.start * 10_000).to_be_bytes());
+            let mut title = chapter.title.as_str();
+            while title.len() > u8::MAX as usize {
+                let mut end = u8::MAX as usize;
+                while !title.is_char_boundary(end) {
+                    end -= 1;
+                }
+                title = &title[..end];
+            }
+            out.push(title.len() as u8);
+            out.extend_from_slice(title.as_bytes());
+        }
+    });
+}
+
+/// 复制tkhd, 修改track_ID和时长
+fn patch_tkhd(tkhd: &[u8], track_id: u32, duration: u64) -> Vec<u8> {
+    let mut tkhd = tkhd.to_vec();
+    let header_size = box_header_size(&tkhd);
+    let version = tkhd[header_size];
+    // 启用, 在movie中使用
+    tkhd[header_size + 3] |= 0x3;
+    if version == 1 {
+        let track_id_at = header_size + 4 + 16;
+        tkhd[track_id_at..track_id_at + 4].copy_from_slice(&track_id.to_be_bytes());
+        let duration_at = track_id_at + 8;
+        tkhd[duration_at..duration_at + 8].copy_from_slice(&duration.to_be_bytes());
+    } else {
+        let track_id_at = header_size + 4 + 8;
+        tkhd[track_id_at..track_id_at + 4].copy_from_slice(&track_id.to_be_bytes());
+        let duration_at = track_id_at + 8;
+        tkhd[duration_at..duration_at + 4]
+            .copy_from_slice(&(duration.min(u32::MAX as u64) as u32).to_be_bytes());
+    }
+    tkhd
+}
+
+/// 复制mdhd, 修改时长
+fn patch_mdhd(mdhd: &[u8], duration: u64) -> Vec<u8> {
+    let mut mdhd = mdhd.to_vec();
+    let header_size = box_header_size(&mdhd);
+    let version = mdhd[header_size];
+    if version == 1 {
+        let duration_at = header_size + 4 + 16 + 4;
+        mdhd[duration_at..duration_at + 8].copy_from_slice(&duration.to_be_bytes());
+    } else {
+        let duration_at = header_size + 4 + 8 + 4;
+        mdhd[duration_at..duration_at + 4]
+            .copy_from_slice(&(duration.min(u32::MAX as u64) as u32).to_be_bytes());
+    }
+    mdhd
+}
+
+fn box_header_size(data: &[u8]) -> usize {
+    if u32::from_be_bytes(data[0..4].try_into().unwrap()) == 1 {
+        16
+    } else {
+        8
+    }
+}
+
+fn write_trak(out: &mut Vec<u8>, track: &Track, track_id: u32, chunk_offsets: &[u64]) {
+    write_box(out, b"trak", |out| {
+        out.extend_from_slice(&patch_tkhd(&track.tkhd, track_id, track.movie_duration()));
+        let presentation_start = track.presentation_start();
+        if presentation_start > 0 {
+            write_box(out, b"edts", |out| {
+                write_full_box(out, b"elst", 1, 0, |out| {
+                    put_u32(out, 1);
+                    out.extend_from_slice(&track.movie_duration().to_be_bytes());
+                    out.extend_from_slice(&presentation_start.to_be_bytes());
+                    put_u32(out, 0x00010000);
+                });
+            });
+        }
+        write_box(out, b"mdia", |out| {
+            out.extend_from_slice(&patch_mdhd(&track.mdhd, track.duration()));
+            out.extend_from_slice(&track.hdlr);
+            write_box(out, b"minf", |out| {
+                if let Some(media_header) = &track.media_header {
+                    out.extend_from_slice(media_header);
+                }
+                match &track.dinf {
+                    Some(dinf) => out.extend_from_slice(dinf),
+                    None => write_box(out, b"dinf", |out| {
+                        write_full_box(out, b"dref", 0, 0, |out| {
+                            put_u32(out, 1);
+                            write_full_box(out, b"url ", 0, 1, |_| {});
+                        });
+                    }),
+                }
+                write_box(out, b"stbl", |out| write_stbl(out, track, chunk_offsets));
+            });
+        });
+    });
+}
+
+fn write_stbl(out: &mut Vec<u8>, track: &Track, chunk_offsets: &[u64]) {
+    out.extend_from_slice(&track.stsd);
+    // stts
+    let mut stts: Vec<(u32, u32)> = vec![];
+    for sample in &track.samples {
+        match stts.last_mut() {
+            Some(last) if last.1 == sample.duration => last.0 += 1,
+            _ => stts.push((1, sample.duration)),
+        }
+    }
+    write_full_box(out, b"stts", 0, 0, |out| {
+        put_u32(out, stts.len() as u32);
+        for (count, delta) in &stts {
+            put_u32(out, *count);
+            put_u32(out, *delta);
+        }
+    });
+    // ctts, 有B帧时需要
+    if track.samples.iter().any(|x| x.composition_offset != 0) {
+        let mut ctts: Vec<(u32, i32)> = vec![];
+        for sample in &track.samples {
+            match ctts.last_mut() {
+                Some(last) if last.1 == sample.composition_offset => last.0 += 1,
+                _ => ctts.push((1, sample.composition_offset)),
+            }
+        }
+        let version = if ctts.iter().any(|x| x.1 < 0) { 1 } else { 0 };
+        write_full_box(out, b"ctts", version, 0, |out| {
+            put_u32(out, ctts.len() as u32);
+            for (count, offset) in &ctts {
+                put_u32(out, *count);
+                put_u32(out, *offset as u32);
+            }
+        });
+    }
+    // stss, 全部是关键帧时不需要
+    if track.samples.iter().any(|x| x.flags & NON_SYNC_SAMPLE != 0) {
+        let sync_samples = track
+            .samples
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.flags & NON_SYNC_SAMPLE == 0)
+            .map(|(i, _)| i as u32 + 1)
+            .collect::<Vec<_>>();
+        write_full_box(out, b"stss", 0, 0, |out| {
+            put_u32(out, sync_samples.len() as u32);
+            for sample in sync_samples {
+                put_u32(out, sample);
+            }
+        });
+    }
+    // stsc
+    let mut stsc: Vec<(u32, u32)> = vec![];
+    for (index, chunk) in track.chunks.iter().enumerate() {
+        match stsc.last() {
+            Some(last) if last.1 == chunk.sample_count => (),
+            _ => stsc.push((index as u32 + 1, chunk.sample_count)),
+        }
+    }
+    write_full_box(out, b"stsc", 0, 0, |out| {
+        put_u32(out, stsc.len() as u32);
+        for (first_chunk, samples_per_chunk) in &stsc {
+            put_u32(out, *first_chunk);
+            put_u32(out, *samples_per_chunk);
+            put_u32(out, 1);
+        }
+    });
+    // stsz
+    write_full_box(out, b"stsz", 0, 0, |out| {
+        put_u32(out, 0);
+        put_u32(out, track.samples.len() as u32);
+        for sample in &track.samples {
+            put_u32(out, sample.size);
+        }
+    });
+    // stco, 超过4G时使用co64
+    if chunk_offsets.iter().any(|x| *x > u32::MAX as u64) {
+        write_full_box(out, b"co64", 0, 0, |out| {
+            put_u32(out, chunk_offsets.len() as u32);
+            for offset in chunk_offsets {
+                out.extend_from_slice(&offset.to_be_bytes());
+            }
+        });
+    } else {
+        write_full_box(out, b"stco", 0, 0, |out| {
+            put_u32(out, chunk_offsets.len() as u32);
+            for offset in chunk_offsets {
+                put_u32(out, *offset as u32);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用的fMP4, 由 testdata/make_fixtures.py 生成
+    fn testdata(name: &str) -> String {
+        format!("{}/src/mp4/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    /// sample的内容, 第i个sample的每个字节都是 first_value + i
+    fn sample_bytes(sizes: &[usize], first_value: u8) -> Vec<u8> {
+        sizes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, size)| vec![first_value + i as u8; *size])
+            .collect()
+    }
+
+    fn chunk_bytes(data: &[u8], offset: u64, size: u64) -> Vec<u8> {
+        data[offset as usize..(offset + size) as usize].to_vec()
+    }
+
+    /// trak中stbl的子box
+    fn stbl_child<'a>(trak: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+        let mdia = require_child(trak, b"mdia").unwrap();
+        let minf = require_child(mdia.2, b"minf").unwrap();
+        let stbl = require_child(minf.2, b"stbl").unwrap();
+        find_child(stbl.2, box_type).unwrap().map(|x| x.2)
+    }
+
+    /// stco或co64中chunk的位置
+    fn output_chunk_offsets(trak: &[u8]) -> Vec<u64> {
+        if let Some(co64) = stbl_child(trak, b"co64") {
+            let mut reader = Reader::new(co64);
+            reader.u32().unwrap();
+            let count = reader.u32().unwrap();
+            return (0..count).map(|_| reader.u64().unwrap()).collect();
+        }
+        let stco = stbl_child(trak, b"stco").unwrap();
+        let mut reader = Reader::new(stco);
+        reader.u32().unwrap();
+        let count = reader.u32().unwrap();
+        (0..count).map(|_| reader.u32().unwrap() as u64).collect()
+    }
+
+    fn traks(moov: &[u8]) -> Vec<&[u8]> {
+        children(moov)
+            .unwrap()
+            .into_iter()
+            .filter(|x| &x.0 == b"trak")
+            .map(|x| x.2)
+            .collect()
+    }
+
+    #[test]
+    fn read_moof_with_data_offset() {
+        let path = testdata("video.mp4");
+        let data = std::fs::read(&path).unwrap();
+        let track = read_track(&path).unwrap();
+        assert_eq!(track.timescale, 1000);
+        assert_eq!(track.samples.len(), 7);
+        assert_eq!(track.chunks.len(), 3);
+        let chunk = &track.chunks[0];
+        assert_eq!(chunk.sample_count, 3);
+        assert_eq!(chunk.decode_time, 0);
+        assert_eq!(
+            chunk_bytes(&data, chunk.offset, chunk.size),
+            sample_bytes(&[5, 6, 7], 0x10)
+        );
+        // first_sample_flags只用于第一个sample, 其他的使用trex中的默认值
+        assert_eq!(track.samples[0].flags & NON_SYNC_SAMPLE, 0);
+        assert_ne!(track.samples[1].flags & NON_SYNC_SAMPLE, 0);
+        assert_ne!(track.samples[2].flags & NON_SYNC_SAMPLE, 0);
+        // trun version 1 中的负数
+        let offsets = track.samples[..3]
+            .iter()
+            .map(|x| x.composition_offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![40, 80, -40]);
+        assert!(track.samples.iter().all(|x| x.duration == 40));
+    }
+
+    #[test]
+    fn read_moof_without_data_offset() {
+        let path = testdata("video.mp4");
+        let data = std::fs::read(&path).unwrap();
+        let track = read_track(&path).unwrap();
+        // 第一个trun从tfhd的base_data_offset开始, 第二个trun紧接着第一个
+        let first = &track.chunks[1];
+        let second = &track.chunks[2];
+        assert_eq!(first.sample_count, 2);
+        assert_eq!(first.decode_time, 120);
+        assert_eq!(second.decode_time, 200);
+        assert_eq!(second.offset, first.offset + first.size);
+        let expected = sample_bytes(&[8, 9, 10, 11], 0x20);
+        assert_eq!(chunk_bytes(&data, first.offset, first.size), expected[..17]);
+        assert_eq!(
+            chunk_bytes(&data, second.offset, second.size),
+            expected[17..]
+        );
+        assert!(track.samples[3..]
+            .iter()
+            .all(|x| x.flags & NON_SYNC_SAMPLE != 0 && x.composition_offset == 0));
+    }
+
+    #[test]
+    fn write_mp4_faststart() {
+        let video_path = testdata("video.mp4");
+        let audio_path = testdata("audio.mp4");
+        let tracks = [
+            read_track(&video_path).unwrap(),
+            read_track(&audio_path).unwrap(),
+        ];
+        let output = std::env::temp_dir()
+            .join(format!("bili-cli-mp4-test-{}.mp4", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        write_mp4(&tracks, &[], &output).unwrap();
+        let data = std::fs::read(&output).unwrap();
+        let _ = std::fs::remove_file(&output);
+        let boxes = children(&data).unwrap();
+        let box_types = boxes.iter().map(|x| &x.0).collect::<Vec<_>>();
+        assert_eq!(box_types, vec![b"ftyp", b"moov", b"mdat"]);
+        // 每个chunk的位置都指向输入文件中相同的内容
+        let traks = traks(boxes[1].2);
+        assert_eq!(traks.len(), 2);
+        for (track, trak) in tracks.iter().zip(traks) {
+            let input = std::fs::read(&track.file).unwrap();
+            let offsets = output_chunk_offsets(trak);
+            assert_eq!(offsets.len(), track.chunks.len());
+            for (chunk, offset) in track.chunks.iter().zip(offsets) {
+                assert_eq!(
+                    chunk_bytes(&data, offset, chunk.size),
+                    chunk_bytes(&input, chunk.offset, chunk.size)
+                );
+            }
+        }
+        // 视频有负数的composition offset, 只有第一个sample是关键帧
+        let ctts = stbl_child(traks_of(&data)[0], b"ctts").unwrap();
+        assert_eq!(ctts[0], 1);
+        let stss = stbl_child(traks_of(&data)[0], b"stss").unwrap();
+        assert_eq!(&stss[4..], &[0, 0, 0, 1, 0, 0, 0, 1]);
+        assert!(stbl_child(traks_of(&data)[1], b"ctts").is_none());
+    }
+
+    fn traks_of(data: &[u8]) -> Vec<&[u8]> {
+        traks(require_child(data, b"moov").unwrap().2)
+    }
+
+    /// edts中elst的 (时长, 开始的时间)
+    fn elst(trak: &[u8]) -> Option<(u64, i64)> {
+        let edts = find_child(trak, b"edts").unwrap()?;
+        let elst = require_child(edts.2, b"elst").unwrap();
+        let mut reader = Reader::new(elst.2);
+        assert_eq!(reader.u32().unwrap() >> 24, 1);
+        assert_eq!(reader.u32().unwrap(), 1);
+        Some((reader.u64().unwrap(), reader.u64().unwrap() as i64))
+    }
+
+    #[test]
+    fn elst_for_presentation_start() {
+        let mut track = read_track(&testdata("video.mp4")).unwrap();
+        let offsets = vec![vec![0; track.chunks.len()]];
+        // 第一帧的显示时间为40
+        let moov = build_moov(std::slice::from_ref(&track), &[], &offsets);
+        let trak = traks(require_child(&moov, b"moov").unwrap().2)[0];
+        assert_eq!(elst(trak), Some((280, 40)));
+        // 显示时间从0开始时不需要编辑列表
+        for sample in track.samples.iter_mut() {
+            sample.composition_offset = 0;
+        }
+        let moov = build_moov(std::slice::from_ref(&track), &[], &offsets);
+        let trak = traks(require_child(&moov, b"moov").unwrap().2)[0];
+        assert_eq!(elst(trak), None);
+    }
+
+    #[test]
+    fn layout_switches_to_co64() {
+        let mut track = read_track(&testdata("audio.mp4")).unwrap();
+        let header_size = 40;
+        // 不包含moov时第二个chunk在4G以内, 加上moov之后超过4G
+        let first_size = u32::MAX as u64 - header_size - 10;
+        track.chunks[0].size = first_size;
+        let tracks = [track];
+        let order = chunk_order(&tracks);
+        let moov = layout_moov(&tracks, &[], &order, header_size);
+        let trak = traks(require_child(&moov, b"moov").unwrap().2)[0];
+        assert!(stbl_child(trak, b"stco").is_none());
+        let offsets = output_chunk_offsets(trak);
+        assert_eq!(offsets[0], header_size + moov.len() as u64);
+        assert_eq!(offsets[1], offsets[0] + first_size);
+        assert!(offsets[1] > u32::MAX as u64);
+    }
+}
@@ -0,0 +1,106 @@
+#!/usr/bin/env python3
+"""生成mp4.rs测试使用的fMP4文件, 在这个文件夹中运行 python3 make_fixtures.py
+
+video.mp4 : 视频轨道, timescale 1000, 两个moof
+  第一个moof : trun带有data_offset和first_sample_flags, version 1, 有负数的composition offset
+  第二个moof : tfhd带有base_data_offset, 两个不带data_offset的trun, 使用trex中的sample flags(非关键帧)
+audio.mp4 : 音频轨道, timescale 48000, 两个moof, trun带有data_offset
+
+每个sample的内容都是同一个字节, 用于检查合并后的位置
+"""
+import struct
+
+
+def box(box_type, content):
+    return struct.pack(">I", 8 + len(content)) + box_type + content
+
+
+def full_box(box_type, version, flags, content):
+    return box(box_type, struct.pack(">I", version << 24 | flags) + content)
+
+
+MATRIX = struct.pack(">9I", 0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000)
+
+
+def moov(track_id, timescale, handler, media_header, trex_defaults):
+    mvhd = full_box(b"mvhd", 0, 0, struct.pack(">IIII", 0, 0, 1000, 0)
+                    + struct.pack(">IH", 0x10000, 0x100) + bytes(10) + MATRIX + bytes(24)
+                    + struct.pack(">I", track_id + 1))
+    tkhd = full_box(b"tkhd", 0, 3, struct.pack(">IIIII", 0, 0, track_id, 0, 0) + bytes(8)
+                    + struct.pack(">HHHH", 0, 0, 0, 0) + MATRIX + struct.pack(">II", 0, 0))
+    mdhd = full_box(b"mdhd", 0, 0, struct.pack(">IIIIHH", 0, 0, timescale, 0, 0x55c4, 0))
+    hdlr = full_box(b"hdlr", 0, 0, struct.pack(">I", 0) + handler + bytes(12) + b"\0")
+    dinf = box(b"dinf", full_box(b"dref", 0, 0, struct.pack(">I", 1) + full_box(b"url ", 0, 1, b"")))
+    stbl = box(b"stbl", full_box(b"stsd", 0, 0, struct.pack(">I", 0))
+               + full_box(b"stts", 0, 0, struct.pack(">I", 0))
+               + full_box(b"stsc", 0, 0, struct.pack(">I", 0))
+               + full_box(b"stsz", 0, 0, struct.pack(">II", 0, 0))
+               + full_box(b"stco", 0, 0, struct.pack(">I", 0)))
+    minf = box(b"minf", media_header + dinf + stbl)
+    trak = box(b"trak", tkhd + box(b"mdia", mdhd + hdlr + minf))
+    trex = full_box(b"trex", 0, 0, struct.pack(">IIIII", track_id, 1, *trex_defaults))
+    return box(b"moov", mvhd + trak + box(b"mvex", trex))
+
+
+def tfdt(decode_time):
+    return full_box(b"tfdt", 1, 0, struct.pack(">Q", decode_time))
+
+
+def mdat(sizes, first_value):
+    return box(b"mdat", b"".join(bytes([first_value + i]) * size for i, size in enumerate(sizes)))
+
+
+FTYP = box(b"ftyp", b"iso6" + struct.pack(">I", 0) + b"iso6mp41")
+
+
+def video():
+    header = FTYP + moov(1, 1000, b"vide", full_box(b"vmhd", 0, 1, bytes(8)), (40, 0, 0x10000))
+    # 第一个moof, data_offset为mdat的内容相对moof的位置
+    sizes1 = [5, 6, 7]
+    offsets1 = [40, 80, -40]
+
+    def moof1(data_offset):
+        trun = full_box(b"trun", 1, 0x1 | 0x4 | 0x200 | 0x800,
+                        struct.pack(">IiI", len(sizes1), data_offset, 0x02000000)
+                        + b"".join(struct.pack(">Ii", size, offset) for size, offset in zip(sizes1, offsets1)))
+        tfhd = full_box(b"tfhd", 0, 0x20000, struct.pack(">I", 1))
+        return box(b"moof", full_box(b"mfhd", 0, 0, struct.pack(">I", 1)) + box(b"traf", tfhd + tfdt(0) + trun))
+
+    first = moof1(len(moof1(0)) + 8)
+    first += mdat(sizes1, 0x10)
+    # 第二个moof, tfhd中的base_data_offset为mdat的内容在文件中的位置
+    runs = [[8, 9], [10, 11]]
+    start = len(header) + len(first)
+
+    def moof2(base_offset):
+        truns = b"".join(full_box(b"trun", 0, 0x200, struct.pack(">I", len(run))
+                                  + b"".join(struct.pack(">I", size) for size in run)) for run in runs)
+        tfhd = full_box(b"tfhd", 0, 0x1 | 0x8, struct.pack(">IQI", 1, base_offset, 40))
+        return box(b"moof", full_box(b"mfhd", 0, 0, struct.pack(">I", 2)) + box(b"traf", tfhd + tfdt(120) + truns))
+
+    second = moof2(start + len(moof2(0)) + 8)
+    second += mdat([size for run in runs for size in run], 0x20)
+    return header + first + second
+
+
+def audio():
+    header = FTYP + moov(1, 48000, b"soun", full_box(b"smhd", 0, 0, bytes(4)), (1024, 0, 0))
+    data = b""
+    for index, decode_time in enumerate([0, 4096]):
+        sizes = [3, 3, 3, 3]
+
+        def moof(data_offset):
+            trun = full_box(b"trun", 0, 0x1 | 0x200, struct.pack(">Ii", len(sizes), data_offset)
+                            + b"".join(struct.pack(">I", size) for size in sizes))
+            tfhd = full_box(b"tfhd", 0, 0x20000, struct.pack(">I", 1))
+            return box(b"moof", full_box(b"mfhd", 0, 0, struct.pack(">I", index + 1))
+                       + box(b"traf", tfhd + tfdt(decode_time) + trun))
+
+        data += moof(len(moof(0)) + 8) + mdat(sizes, 0xA0 + index * 4)
+    return header + data
+
+
+with open("video.mp4", "wb") as file:
+    file.write(video())
+with open("audio.mp4", "wb") as file:
+    file.write(audio())
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use crate::chapter::{self, Chapter};
+use crate::down::print_line;
+use crate::{app, config};
+
+/// 合并音频和视频的方式
+pub(crate) trait Muxer {
+    /// 名称, 和 --muxer 的值相同
+    fn name(&self) -> &'static str;
+
+    /// 当前环境是否可以使用
+    fn available(&self) -> bool;
+
+    /// 合并视频和音频到output, 同时写入章节
+    fn merge(
+        &self,
+        video: &str,
+        audio: &str,
+        chapters: &[Chapter],
+        output: &str,
+    ) -> crate::Result<()>;
+}
+
+/// 编译进来的全部合并方式, 按照auto时尝试的顺序排列
+/// 优先使用内置的封装, 不需要安装ffmpeg, 遇到不支持的文件时再使用ffmpeg
+fn muxers() -> Vec<Box<dyn Muxer>> {
+    vec![
+        Box::new(crate::mp4::NativeMuxer),
+        #[cfg(feature = "ffmpeg_api")]
+        Box::new(crate::ffmpeg::LibavMuxer),
+        Box::new(crate::ffmpeg::FfmpegMuxer),
+    ]
+}
+
+/// 使用的合并方式: --muxer > 配置中的muxer
+fn muxer_name() -> String {
+    app::muxer_value().unwrap_or_else(|| config::config_value("muxer"))
+}
+
+/// 合并音频和视频
+/// auto时按照顺序使用可用的合并方式, 失败时尝试下一个
+pub(crate) fn merge_file(
+    video: &str,
+    audio: &str,
+    chapters: &[Chapter],
+    output: &str,
+) -> crate::Result<()> {
+    let chapters = if chapter::embed_chapters() {
+        chapters
+    } else {
+        &[]
+    };
+    let name = muxer_name();
+    if name != "auto" {
+        let muxer = muxers()
+            .into_iter()
+            .find(|muxer| muxer.name() == name)
+            .ok_or_else(|| {
+                anyhow::Error::msg(format!(
+                    "不支持的合并方式 : {}, libav需要使用 --features ffmpeg_api 构建",
+                    name
+                ))
+            })?;
+        if !muxer.available() {
+            return Err(anyhow::Error::msg(
+                "未找到ffmpeg, 请先安装ffmpeg, 或者检查 ffmpeg_path 配置, 也可以使用 --muxer native",
+            ));
+        }
+        return muxer.merge(video, audio, chapters, output);
+    }
+    let mut errors = vec![];
+    for muxer in muxers().into_iter().filter(|muxer| muxer.available()) {
+        match muxer.merge(video, audio, chapters, output) {
+            Ok(_) => return Ok(()),
+            Err(err) => {
+                print_line(format!(" > 使用 {} 合并失败 : {}", muxer.name(), err));
+                let _ = std::fs::remove_file(output);
+                errors.push(format!("{} : {}", muxer.name(), err));
+            }
+        }
+    }
+    Err(anyhow::Error::msg(format!(
+        "未能合并音频和视频 ({})",
+        errors.join(" / ")
+    )))
+}
+
+/// 合并本地的视频和音频文件
+pub(crate) async fn merge() -> crate::Result<()> {
+    let (video, audio, output) = app::merge_files_value();
+    if Path::new(output.as_str()).exists() {
+        return Err(anyhow::Error::msg(format!("文件已存在 : {}", output)));
+    }
+    crate::transcode::merge_file(video.as_str(), audio.as_str(), &[], output.as_str())?;
+    print_line(format!("合并完成 : {}", output));
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Context;
+use bilirust::{FNVAL_DASH, VIDEO_QUALITY_4K};
+use itertools::Itertools;
+
+use crate::api::{SubtitleLine, WebApi};
+use crate::down::{self, BV_PATTERN, SERIES_PATTERN};
+use crate::local::{join_paths, template_dir};
+use crate::region::{self, Region};
+use crate::{app, config, danmaku, http, login_client};
+
+/// 播放地址需要的referer
+pub(crate) const REFERER: &str = "https://www.bilibili.com";
+
+/// 解析到的视频, 音频和视频为 (质量, 地址)
+#[derive(Clone)]
+pub(crate) struct Stream {
+    pub title: String,
+    pub bvid: String,
+    pub cid: i64,
+    pub audio: Vec<(i64, String)>,
+    pub video: Vec<(i64, String)>,
+}
+
+/// 播放视频
+pub(crate) async fn play() -> crate::Result<()> {
+    let url = down::resolve_short_link(app::url_value()).await?;
+    let client = login_client().await?;
+    let stream = resolve_stream(&client, url.as_str()).await?;
+    println!();
+    println!("{}", stream.title);
+    // 和下载相同, 按照配置的清晰度选择
+    let video_ids = stream.video.iter().map(|x| x.0).collect_vec();
+    let formats = video_ids
+        .iter()
+        .map(|id| (down::video_name(*id), *id))
+        .collect_vec();
+    let video_quality = down::choose_video_quality(&video_ids, formats);
+    let audio_ids = stream.audio.iter().map(|x| x.0).collect_vec();
+    let audio_quality = down::choose_audio_quality(&audio_ids);
+    let video_url = stream_url(&stream.video, video_quality).with_context(|| "未找到视频")?;
+    let audio_url = stream_url(&stream.audio, audio_quality).with_context(|| "未找到音频")?;
+    println!(" > 视频 : {}", down::video_name(video_quality));
+    // 弹幕和字幕失败时只打印错误, 不影响播放
+    let mut subtitle_files = vec![];
+    if app::danmaku_value() || app::subtitles_value() {
+        if app::danmaku_value() {
+            match save_danmaku(&client, stream.cid).await {
+                Ok(file) => subtitle_files.push(file),
+                Err(err) => println!(" > 未能读取弹幕 : {}", err),
+            }
+        }
+        if app::subtitles_value() {
+            match save_subtitle(&client, &stream).await {
+                Ok(Some(file)) => subtitle_files.push(file),
+                Ok(None) => println!(" > 没有字幕"),
+                Err(err) => println!(" > 未能读取字幕 : {}", err),
+            }
+        }
+    }
+    launch_player(
+        stream.title.as_str(),
+        video_url.as_str(),
+        audio_url.as_str(),
+        &subtitle_files,
+    )
+}
+
+/// 解析视频或番剧的网址, 取得标题和DASH音视频的地址
+/// 番剧有地区限制并且配置了地区的接口服务器时, 使用地区的接口服务器
+pub(crate) async fn resolve_stream(client: &WebApi, url: &str) -> crate::Result<Stream> {
+    if let Some(find) = BV_PATTERN.find(url) {
+        let bvid = find.as_str().to_owned();
+        let info = client.archive_view(&bvid).await?;
+        let vu = client
+            .play_url(&bvid, info.cid, FNVAL_DASH, VIDEO_QUALITY_4K)
+            .await?;
+        return Ok(Stream {
+            title: info.title,
+            bvid,
+            cid: info.cid,
+            audio: vu
+                .dash
+                .audio
+                .into_iter()
+                .map(|x| (x.id, x.base_url))
+                .collect(),
+            video: vu
+                .dash
+                .video
+                .into_iter()
+                .map(|x| (x.id, x.base_url))
+                .collect(),
+        });
+    }
+    if let Some(find) = SERIES_PATTERN.find(url) {
+        let id = find.as_str();
+        let season = client
+            .pgc_season(config::config_value("api_endpoint").as_str(), id)
+            .await?;
+        // ss为第一集
+        let ep = match id.strip_prefix("ep") {
+            Some(ep_id) => season
+                .episodes
+                .iter()
+                .chain(season.section.iter().flat_map(|x| x.episodes.iter()))
+                .find(|ep| ep.id.to_string() == ep_id),
+            None => season.episodes.first(),
+        }
+        .with_context(|| "未找到剧集")?;
+        let bvid = if !ep.bvid.is_empty() {
+            ep.bvid.clone()
+        } else {
+            bilirust::av_to_bv(ep.aid)
+        };
+        let title = format!("{} {} {}", season.title, ep.title, ep.long_title)
+            .trim()
+            .to_owned();
+        let region = Region::of_season(&season);
+        let (audio, video) = if region.use_endpoint() {
+            let endpoint = region.endpoint().unwrap();
+            region::region_dash(client, region, &endpoint, ep.aid, &bvid, ep.cid).await?
+        } else {
+            let vu = client
+                .play_url(&bvid, ep.cid, FNVAL_DASH, VIDEO_QUALITY_4K)
+                .await
+                .map_err(|err| region::region_error(region, err))?;
+            (
+                vu.dash
+                    .audio
+                    .into_iter()
+                    .map(|x| (x.id, x.base_url))
+                    .collect(),
+                vu.dash
+                    .video
+                    .into_iter()
+                    .map(|x| (x.id, x.base_url))
+                    .collect(),
+            )
+        };
+        return Ok(Stream {
+            title,
+            bvid,
+            cid: ep.cid,
+            audio,
+            video,
+        });
+    }
+    Err(anyhow::Error::msg("只能播放视频(BV)和番剧(ep/ss)的网址"))
+}
+
+/// 质量对应的地址
+pub(crate) fn stream_url(streams: &[(i64, String)], quality: i64) -> Option<String> {
+    streams.iter().find(|x| x.0 == quality).map(|x| x.1.clone())
+}
+
+/// 将弹幕转换为ASS字幕, 保存在临时文件夹
+async fn save_danmaku(web_api: &WebApi, cid: i64) -> crate::Result<String> {
+    let xml = web_api.danmaku_xml(cid).await?;
+    let file = join_paths(vec![
+        template_dir().as_str(),
+        format!("{}.danmaku.ass", cid).as_str(),
+    ]);
+    tokio::fs::write(file.as_str(), danmaku::danmaku_ass(xml.as_str())).await?;
+    println!(" > 弹幕 : {}", file);
+    Ok(file)
+}
+
+/// 将字幕转换为SRT, 保存在临时文件夹, 没有字幕时返回None
+async fn save_subtitle(web_api: &WebApi, stream: &Stream) -> crate::Result<Option<String>> {
+    let subtitles = web_api
+        .player_info(stream.bvid.as_str(), stream.cid)
+        .await?
+        .subtitle
+        .subtitles;
+    // AI生成的字幕的语言以 ai- 开头
+    let subtitle = match subtitles
+        .iter()
+        .find(|x| !x.lan.starts_with("ai-"))
+        .or_else(|| subtitles.first())
+    {
+        Some(subtitle) => subtitle,
+        None => return Ok(None),
+    };
+    let body = web_api
+        .subtitle_body(subtitle.subtitle_url.as_str())
+        .await?;
+    let file = join_paths(vec![
+        template_dir().as_str(),
+        format!("{}.{}.srt", stream.cid, subtitle.lan).as_str(),
+    ]);
+    tokio::fs::write(file.as_str(), subtitle_srt(&body.body)).await?;
+    println!(" > 字幕 : {} ({})", subtitle.lan_doc, file);
+    Ok(Some(file))
+}
+
+fn subtitle_srt(lines: &[SubtitleLine]) -> String {
+    let mut content = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        content.push_str(
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                index + 1,
+                srt_time(line.from),
+                srt_time(line.to),
+                line.content
+            )
+            .as_str(),
+        );
+    }
+    content
+}
+
+/// SRT的时间 时:分:秒,毫秒
+fn srt_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3600000,
+        millis / 60000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// 启动播放器并等待播放器退出
+fn launch_player(
+    title: &str,
+    video: &str,
+    audio: &str,
+    subtitle_files: &[String],
+) -> crate::Result<()> {
+    let player = config::config_value("player");
+    let player_args = config::config_value("player_args");
+    let user_agent = http::user_agent();
+    let args = if !player_args.trim().is_empty() {
+        // 替换后为空的参数会被忽略, 例如没有字幕时的 {subtitle}
+        let subtitle = subtitle_files.first().map(String::as_str).unwrap_or("");
+        player_args
+            .split_whitespace()
+            .map(|arg| {
+                arg.replace("{video}", video)
+                    .replace("{audio}", audio)
+                    .replace("{title}", title)
+                    .replace("{referer}", REFERER)
+                    .replace("{user_agent}", user_agent.as_str())
+                    .replace("{subtitle}", subtitle)
+            })
+            .filter(|arg| !arg.is_empty())
+            .collect_vec()
+    } else {
+        let name = Path::new(player.as_str())
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match name.as_str() {
+            "mpv" => mpv_args(title, video, audio, user_agent.as_str(), subtitle_files),
+            "vlc" => vlc_args(title, video, audio, user_agent.as_str(), subtitle_files),
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "不知道如何使用播放器 {}, 请配置 player_args",
+                    player
+                )))
+            }
+        }
+    };
+    println!(" > 使用 {} 播放", player);
+    let status = Command::new(player.as_str())
+        .args(&args)
+        .status()
+        .with_context(|| format!("未能启动播放器 {}, 请先安装, 或者检查 player 配置", player))?;
+    if !status.success() {
+        println!(" > 播放器退出 : {}", status);
+    }
+    Ok(())
+}
+
+/// 有弹幕和字幕时, 弹幕作为主字幕, 字幕作为第二字幕
+fn mpv_args(
+    title: &str,
+    video: &str,
+    audio: &str,
+    user_agent: &str,
+    subtitle_files: &[String],
+) -> Vec<String> {
+    let mut args = vec![
+        format!("--force-media-title={}", title),
+        format!("--referrer={}", REFERER),
+        format!("--user-agent={}", user_agent),
+        format!("--audio-file={}", audio),
+    ];
+    for file in subtitle_files {
+        args.push(format!("--sub-file={}", file));
+    }
+    if subtitle_files.len() > 1 {
+        args.push("--sid=1".to_owned());
+        args.push("--secondary-sid=2".to_owned());
+    }
+    args.push(video.to_owned());
+    args
+}
+
+/// vlc只能加载一个字幕文件
+fn vlc_args(
+    title: &str,
+    video: &str,
+    audio: &str,
+    user_agent: &str,
+    subtitle_files: &[String],
+) -> Vec<String> {
+    let mut args = vec![
+        format!("--meta-title={}", title),
+        format!("--http-referrer={}", REFERER),
+        format!("--http-user-agent={}", user_agent),
+        format!("--input-slave={}", audio),
+    ];
+    if let Some(file) = subtitle_files.first() {
+        args.push(format!("--sub-file={}", file));
+    }
+    args.push(video.to_owned());
+    args
+}
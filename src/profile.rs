@@ -0,0 +1,114 @@
+use std::process::exit;
+
+use crate::local::{delete_properties, load_properties_with_prefix, load_property, save_property};
+use crate::{app, token};
+
+/// 未指定时使用的账号, 对应旧版本保存的web_token
+pub(crate) const DEFAULT_PROFILE: &str = "default";
+
+const DEFAULT_PROFILE_KEY: &str = "default_profile";
+const WEB_TOKEN_KEY: &str = "web_token";
+
+/// 账号相关的数据在配置表中的key, 默认账号不加后缀
+pub(crate) fn profile_key(key: &str, profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        key.to_owned()
+    } else {
+        format!("{}.{}", key, profile)
+    }
+}
+
+/// 账号对应的token在配置表中的key
+pub(crate) fn web_token_key(profile: &str) -> String {
+    profile_key(WEB_TOKEN_KEY, profile)
+}
+
+/// 当前使用的账号: --profile参数 > 设置的默认账号 > default
+pub(crate) async fn active_profile() -> crate::Result<String> {
+    if let Some(profile) = app::profile_value() {
+        return Ok(profile);
+    }
+    let profile = load_property(DEFAULT_PROFILE_KEY.to_owned()).await?;
+    if profile.is_empty() {
+        Ok(DEFAULT_PROFILE.to_owned())
+    } else {
+        Ok(profile)
+    }
+}
+
+/// 已经登录的全部账号
+pub(crate) async fn list_profiles() -> crate::Result<Vec<String>> {
+    let mut profiles = vec![];
+    if !load_property(WEB_TOKEN_KEY.to_owned()).await?.is_empty() {
+        profiles.push(DEFAULT_PROFILE.to_owned());
+    }
+    let prefix = format!("{}.", WEB_TOKEN_KEY);
+    for (k, v) in load_properties_with_prefix(prefix.as_str()).await? {
+        if !v.is_empty() {
+            profiles.push(k[prefix.len()..].to_owned());
+        }
+    }
+    Ok(profiles)
+}
+
+/// 账号管理
+pub(crate) async fn profile() -> crate::Result<()> {
+    match app::nested_subcommand() {
+        Some(subcommand) => match subcommand.as_str() {
+            "list" => list().await?,
+            "remove" => remove(app::profile_name_value()).await?,
+            "default" => set_default(app::profile_name_value()).await?,
+            _ => app::print_help()?,
+        },
+        None => list().await?,
+    }
+    Ok(())
+}
+
+async fn list() -> crate::Result<()> {
+    let active = active_profile().await?;
+    let profiles = list_profiles().await?;
+    if profiles.is_empty() {
+        println!("没有登录的账号");
+        return Ok(());
+    }
+    for profile in profiles {
+        if profile == active {
+            println!("* {}", profile);
+        } else {
+            println!("  {}", profile);
+        }
+    }
+    Ok(())
+}
+
+async fn remove(profile: String) -> crate::Result<()> {
+    if !list_profiles().await?.contains(&profile) {
+        println!("账号不存在 : {}", profile);
+        exit(1);
+    }
+    // token和token信息中的refresh_token一起删除, 不留下可以使用的登录信息
+    let mut keys = vec![
+        web_token_key(profile.as_str()),
+        token::token_meta_key(profile.as_str()),
+    ];
+    if load_property(DEFAULT_PROFILE_KEY.to_owned()).await? == profile {
+        keys.push(DEFAULT_PROFILE_KEY.to_owned());
+    }
+    delete_properties(keys).await?;
+    println!("已删除账号 : {}", profile);
+    Ok(())
+}
+
+async fn set_default(profile: String) -> crate::Result<()> {
+    if !list_profiles().await?.contains(&profile) {
+        println!(
+            "账号不存在, 请先使用 bili-cli login --profile {} 登录",
+            profile
+        );
+        exit(1);
+    }
+    save_property(DEFAULT_PROFILE_KEY.to_owned(), profile.clone()).await?;
+    println!("默认账号 : {}", profile);
+    Ok(())
+}
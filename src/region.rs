@@ -0,0 +1,268 @@
+use crate::api::{ApiError, PgcSeason, WebApi};
+use crate::config::{config_value, config_value_opt};
+
+/// 番剧可以播放的地区
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Region {
+    Mainland,
+    HongKong,
+    Taiwan,
+    SouthEastAsia,
+}
+
+impl Region {
+    /// 地区的名称
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Region::Mainland => "中国大陆",
+            Region::HongKong => "港澳台",
+            Region::Taiwan => "台湾",
+            Region::SouthEastAsia => "东南亚",
+        }
+    }
+
+    /// 这个地区使用的接口服务器的配置
+    pub(crate) fn config_key(&self) -> &'static str {
+        match self {
+            Region::Mainland => "api_endpoint",
+            Region::HongKong => "hk_endpoint",
+            Region::Taiwan => "tw_endpoint",
+            Region::SouthEastAsia => "sea_endpoint",
+        }
+    }
+
+    /// 请求代理服务器时的area参数
+    fn area(&self) -> &'static str {
+        match self {
+            Region::Mainland => "cn",
+            Region::HongKong => "hk",
+            Region::Taiwan => "tw",
+            Region::SouthEastAsia => "th",
+        }
+    }
+
+    /// 配置的接口服务器, 没有配置时返回None
+    pub(crate) fn endpoint(&self) -> Option<String> {
+        config_value_opt(self.config_key())
+    }
+
+    /// 是否使用地区的接口服务器下载, 没有配置时使用默认的方式
+    pub(crate) fn use_endpoint(&self) -> bool {
+        *self != Region::Mainland && self.endpoint().is_some()
+    }
+
+    /// 番剧的地区, 可以在中国大陆播放或者没有地区信息时没有地区限制
+    pub(crate) fn of_season(season: &PgcSeason) -> Region {
+        let regions = season
+            .areas
+            .iter()
+            .filter_map(|area| Region::from_area(area.name.as_str()))
+            .collect::<Vec<Region>>();
+        if regions.contains(&Region::Mainland) {
+            return Region::Mainland;
+        }
+        regions.first().copied().unwrap_or(Region::Mainland)
+    }
+
+    /// 地区的名称对应的地区, 不能识别时返回None
+    fn from_area(name: &str) -> Option<Region> {
+        let name = name
+            .replace('灣', "湾")
+            .replace('門', "门")
+            .replace("東南亞", "东南亚");
+        if name.contains("大陆") {
+            Some(Region::Mainland)
+        } else if name.contains("港澳") || name.contains("香港") || name.contains("澳门") {
+            Some(Region::HongKong)
+        } else if name.contains("台湾") {
+            Some(Region::Taiwan)
+        } else if name.contains("东南亚") || name.contains("泰国") {
+            Some(Region::SouthEastAsia)
+        } else {
+            None
+        }
+    }
+}
+
+/// 取得番剧的信息和地区
+pub(crate) async fn fetch_season(
+    web_api: &WebApi,
+    season_id: i64,
+) -> crate::Result<(PgcSeason, Region)> {
+    let season = web_api
+        .pgc_season(
+            config_value("api_endpoint").as_str(),
+            format!("ss{}", season_id).as_str(),
+        )
+        .await?;
+    let region = Region::of_season(&season);
+    Ok((season, region))
+}
+
+/// 使用地区的接口服务器取得播放地址, 返回音频和视频的 (质量, 地址)
+pub(crate) async fn region_dash(
+    web_api: &WebApi,
+    region: Region,
+    endpoint: &str,
+    aid: i64,
+    bvid: &str,
+    cid: i64,
+) -> crate::Result<(Vec<(i64, String)>, Vec<(i64, String)>)> {
+    let play_url = web_api
+        .pgc_play_url(endpoint, aid, bvid, cid, region.area())
+        .await
+        .map_err(|err| region_error(region, err))?;
+    let audio = play_url
+        .dash
+        .audio
+        .into_iter()
+        .map(|x| (x.id, x.base_url))
+        .collect();
+    let video = play_url
+        .dash
+        .video
+        .into_iter()
+        .map(|x| (x.id, x.base_url))
+        .collect();
+    Ok((audio, video))
+}
+
+/// 是否是因为地区限制不能播放
+pub(crate) fn is_region_locked(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<ApiError>() {
+        Some(err) => err.is_region_locked(),
+        // 没有错误码的错误
+        None => err.to_string().contains("地区"),
+    }
+}
+
+/// 地区限制时说明原因和解决办法, 其他错误原样返回
+pub(crate) fn region_error(region: Region, err: anyhow::Error) -> anyhow::Error {
+    if !is_region_locked(&err) {
+        return err;
+    }
+    match region {
+        Region::Mainland => anyhow::Error::msg(format!(
+            "当前网络所在的地区不能观看这个视频 ({}), 请使用 proxy 配置可以观看的地区的代理服务器",
+            err
+        )),
+        region => anyhow::Error::msg(format!(
+            "这个番剧仅限{}地区观看 ({}), 请使用 bili-cli config set {} <地址> 配置这个地区的接口服务器, 或者使用 proxy 配置这个地区的代理服务器",
+            region.name(),
+            err,
+            region.config_key()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Once;
+
+    use super::*;
+    use crate::api::PgcArea;
+    use crate::{app, http};
+
+    static INIT: Once = Once::new();
+
+    /// 代替接口服务器的本地服务, 按照season_id返回番剧的信息
+    fn season_json(season_id: &str) -> String {
+        let areas = match season_id {
+            "1" => r#"[{"id":2,"name":"中国港澳台"}]"#,
+            "2" => r#"[{"id":1,"name":"中国大陆"},{"id":2,"name":"中国港澳台"}]"#,
+            "3" => r#"[{"id":3,"name":"中国台湾"}]"#,
+            _ => return r#"{"code":-404,"message":"啥都木有"}"#.to_owned(),
+        };
+        format!(
+            r#"{{"code":0,"message":"success","result":{{"season_id":{},"title":"测试","areas":{}}}}}"#,
+            season_id, areas
+        )
+    }
+
+    /// 启动本地服务并将 api_endpoint 指向它
+    fn init_endpoint() {
+        INIT.call_once(|| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            std::env::set_var(
+                "BILI_CLI_API_ENDPOINT",
+                format!("http://{}", listener.local_addr().unwrap()),
+            );
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let season_id = request_line
+                        .split(['?', '&', ' '])
+                        .find_map(|x| x.strip_prefix("season_id="))
+                        .unwrap_or_default();
+                    let body = season_json(season_id);
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .unwrap();
+                }
+            });
+            let _ = app::MATCHES.set(app::app().get_matches_from(["bili-cli"]));
+            http::init_http().unwrap();
+        });
+    }
+
+    fn season(names: &[&str]) -> PgcSeason {
+        PgcSeason {
+            areas: names
+                .iter()
+                .map(|name| PgcArea {
+                    id: 0,
+                    name: name.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn region_of_areas() {
+        assert_eq!(Region::of_season(&season(&[])), Region::Mainland);
+        assert_eq!(
+            Region::of_season(&season(&["中国港澳台"])),
+            Region::HongKong
+        );
+        assert_eq!(Region::of_season(&season(&["中國台灣"])), Region::Taiwan);
+        assert_eq!(Region::of_season(&season(&["泰国"])), Region::SouthEastAsia);
+        assert_eq!(
+            Region::of_season(&season(&["中国港澳台", "中国大陆"])),
+            Region::Mainland
+        );
+        assert_eq!(Region::of_season(&season(&["日本"])), Region::Mainland);
+    }
+
+    #[tokio::test]
+    async fn fetch_season_from_endpoint() {
+        init_endpoint();
+        let web_api = WebApi::new();
+        let (season, region) = fetch_season(&web_api, 1).await.unwrap();
+        assert_eq!(season.season_id, 1);
+        assert_eq!(region, Region::HongKong);
+        assert_eq!(fetch_season(&web_api, 2).await.unwrap().1, Region::Mainland);
+        assert_eq!(fetch_season(&web_api, 3).await.unwrap().1, Region::Taiwan);
+    }
+
+    #[tokio::test]
+    async fn fetch_season_error() {
+        init_endpoint();
+        assert!(fetch_season(&WebApi::new(), 4).await.is_err());
+    }
+}
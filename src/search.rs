@@ -0,0 +1,154 @@
+use dialoguer::{Confirm, MultiSelect};
+use itertools::Itertools;
+
+use crate::api::strip_html;
+use crate::{app, down, web_api};
+
+/// 搜索结果中选中后要下载的内容
+enum SearchTarget {
+    Video(String),
+    Bangumi(i64),
+    User(i64, String),
+}
+
+/// 搜索, 选择后下载
+pub(crate) async fn search() -> crate::Result<()> {
+    let keyword = app::keyword_value();
+    let search_type = app::search_type_value();
+    let order = app::search_order_value();
+    app::check_search_order(search_type, order)?;
+    let duration = app::search_duration_value();
+    let mut page = app::search_page_value();
+    let web_api = web_api().await?;
+    loop {
+        let (header, rows, targets, num_pages) = match search_type {
+            "video" => {
+                let rsp = web_api
+                    .search_video(keyword.as_str(), order, duration, page)
+                    .await?;
+                let rows = rsp
+                    .result
+                    .iter()
+                    .map(|x| {
+                        format!(
+                            "{} | {} | {} | {} | {}",
+                            x.bvid,
+                            x.duration,
+                            x.play,
+                            x.author,
+                            strip_html(x.title.as_str()),
+                        )
+                    })
+                    .collect_vec();
+                let targets = rsp
+                    .result
+                    .into_iter()
+                    .map(|x| SearchTarget::Video(x.bvid))
+                    .collect_vec();
+                (
+                    "BV | 时长 | 播放 | UP主 | 标题",
+                    rows,
+                    targets,
+                    rsp.num_pages,
+                )
+            }
+            "bangumi" => {
+                let rsp = web_api
+                    .search_bangumi(keyword.as_str(), order, page)
+                    .await?;
+                let rows = rsp
+                    .result
+                    .iter()
+                    .map(|x| {
+                        format!(
+                            "ss{} | {} | {} | {}",
+                            x.season_id,
+                            x.season_type_name,
+                            x.ep_size,
+                            strip_html(x.title.as_str()),
+                        )
+                    })
+                    .collect_vec();
+                let targets = rsp
+                    .result
+                    .into_iter()
+                    .map(|x| SearchTarget::Bangumi(x.season_id))
+                    .collect_vec();
+                ("ID | 类型 | 集数 | 标题", rows, targets, rsp.num_pages)
+            }
+            _ => {
+                let rsp = web_api.search_user(keyword.as_str(), order, page).await?;
+                let rows = rsp
+                    .result
+                    .iter()
+                    .map(|x| {
+                        format!(
+                            "{} | LV{} | {} | {} | {}",
+                            x.mid,
+                            x.level,
+                            x.fans,
+                            x.videos,
+                            strip_html(x.uname.as_str()),
+                        )
+                    })
+                    .collect_vec();
+                let targets = rsp
+                    .result
+                    .into_iter()
+                    .map(|x| SearchTarget::User(x.mid, strip_html(x.uname.as_str())))
+                    .collect_vec();
+                (
+                    "MID | 等级 | 粉丝 | 投稿 | 用户名",
+                    rows,
+                    targets,
+                    rsp.num_pages,
+                )
+            }
+        };
+        println!();
+        if rows.is_empty() {
+            println!("没有搜索到结果");
+            return Ok(());
+        }
+        println!("第 {} / {} 页", page, num_pages);
+        println!("  {}", header);
+        let selects = MultiSelect::new()
+            .with_prompt("请选择要下载的内容 (空格选择, 回车确认)")
+            .items(&rows)
+            .interact()
+            .unwrap();
+        if !selects.is_empty() {
+            let targets = targets
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| selects.contains(i))
+                .map(|(_, target)| target)
+                .collect_vec();
+            return down_targets(targets).await;
+        }
+        if page >= num_pages
+            || !Confirm::new()
+                .with_prompt("没有选择任何内容, 是否查看下一页")
+                .default(true)
+                .interact()
+                .unwrap()
+        {
+            return Ok(());
+        }
+        page += 1;
+    }
+}
+
+/// 交给下载流程
+async fn down_targets(targets: Vec<SearchTarget>) -> crate::Result<()> {
+    for target in targets {
+        match target {
+            SearchTarget::Video(bvid) => down::down_bv(bvid).await?,
+            SearchTarget::Bangumi(season_id) => {
+                down::down_series(format!("ss{}", season_id)).await?
+            }
+            SearchTarget::User(mid, name) => down::down_user_videos(mid, name).await?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use hyper::header::{HeaderValue, CONTENT_TYPE, HOST, RANGE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
+
+use crate::api::WebApi;
+use crate::down::{self, BV_PATTERN, SERIES_PATTERN};
+use crate::ffmpeg::FfmpegMuxer;
+use crate::mux::Muxer;
+use crate::play::{self, Stream, REFERER};
+use crate::{app, config, http, login_client};
+
+/// 解析到的播放地址的有效时间比这个长, 超过后重新解析
+const STREAM_CACHE: Duration = Duration::from_secs(30 * 60);
+
+/// 转发给播放器的CDN响应头
+const PROXY_HEADERS: [&str; 6] = [
+    "content-type",
+    "content-length",
+    "content-range",
+    "accept-ranges",
+    "last-modified",
+    "etag",
+];
+
+/// 启动时检查过登录状态的客户端, 请求中不再检查和提示重新登录
+static CLIENT: OnceCell<WebApi> = OnceCell::new();
+
+lazy_static! {
+    /// 解析过的视频, 播放器会使用Range多次请求同一个视频
+    static ref STREAMS: Mutex<HashMap<String, (Instant, Stream)>> = Mutex::new(HashMap::new());
+    /// ffmpeg是否可用, 启动时检查一次
+    static ref FFMPEG_AVAILABLE: bool = FfmpegMuxer.available();
+}
+
+/// 请求的参数不正确, 返回400
+#[derive(Debug)]
+struct BadRequest(String);
+
+impl std::fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
+/// 启动本地HTTP服务, Ctrl+C 退出
+pub(crate) async fn serve() -> crate::Result<()> {
+    let listen = app::serve_listen_value();
+    let addr: SocketAddr = listen
+        .parse()
+        .with_context(|| format!("监听的地址不正确 : {}", listen))?;
+    // 未登录时在启动前退出
+    let _ = CLIENT.set(login_client().await?);
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("未能监听 {}", addr))?
+        .serve(make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(handle))
+        }));
+    println!("监听 : http://{}", addr);
+    if !addr.ip().is_loopback() {
+        println!(
+            "警告 : 监听的不是本机地址, 其他设备也可以使用当前登录的账号播放视频, 只在可信的网络中使用"
+        );
+    }
+    if *FFMPEG_AVAILABLE {
+        println!("视频 : http://{}/mux/BV1814y1p7Uj", addr);
+    } else {
+        println!("没有找到ffmpeg, 不能使用 /mux 实时合并");
+    }
+    println!(
+        "      http://{}/video/BV1814y1p7Uj http://{}/audio/BV1814y1p7Uj",
+        addr, addr
+    );
+    println!("按 Ctrl+C 退出");
+    server
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    println!("{} {}", req.method(), req.uri());
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return Ok(text_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "只支持GET和HEAD",
+        ));
+    }
+    let segments = req
+        .uri()
+        .path()
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .map(str::to_owned)
+        .collect_vec();
+    let result = match segments.as_slice() {
+        [] => Ok(text_response(StatusCode::OK, index_text().as_str())),
+        [route, id] => match video_id(id) {
+            Some(id) => match route.as_str() {
+                "info" => info(&req, id).await,
+                "video" | "audio" => proxy(&req, route, id).await,
+                "mux" => mux(&req, id).await,
+                _ => Ok(not_found()),
+            },
+            None => Ok(not_found()),
+        },
+        _ => Ok(not_found()),
+    };
+    Ok(match result {
+        Ok(rsp) => rsp,
+        Err(err) => {
+            println!(" > {}", err);
+            let status = if err.is::<BadRequest>() {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::BAD_GATEWAY
+            };
+            text_response(status, err.to_string().as_str())
+        }
+    })
+}
+
+fn index_text() -> String {
+    "bili-cli serve\n\
+     \n\
+     /info/<id>   视频的标题和可以使用的清晰度\n\
+     /video/<id>  视频 (支持Range)\n\
+     /audio/<id>  音频 (支持Range)\n\
+     /mux/<id>    使用ffmpeg实时合并的MP4 (需要ffmpeg, 不支持跳转)\n\
+     \n\
+     id 为 BV号 / ep123 / ss123, 不指定 ?quality= 时使用配置的清晰度\n\
+     /video/<id>?quality=80 和 /mux/<id>?quality=80 指定视频的清晰度, /audio/<id>?quality=30280 指定音频质量\n"
+        .to_owned()
+}
+
+/// 路径中的视频, 只能为 BV号 / ep / ss
+fn video_id(segment: &str) -> Option<&str> {
+    BV_PATTERN
+        .find(segment)
+        .or_else(|| SERIES_PATTERN.find(segment))
+        .filter(|find| find.as_str() == segment)
+        .map(|find| find.as_str())
+}
+
+fn text_response(status: StatusCode, text: &str) -> Response<Body> {
+    let mut rsp = Response::new(Body::from(text.to_owned()));
+    *rsp.status_mut() = status;
+    rsp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    rsp
+}
+
+fn not_found() -> Response<Body> {
+    text_response(StatusCode::NOT_FOUND, index_text().as_str())
+}
+
+/// 解析视频, 使用缓存中没有过期的结果
+async fn cached_stream(id: &str) -> crate::Result<Stream> {
+    let cached = {
+        let streams = STREAMS.lock().unwrap();
+        streams
+            .get(id)
+            .filter(|x| x.0.elapsed() < STREAM_CACHE)
+            .map(|x| x.1.clone())
+    };
+    if let Some(stream) = cached {
+        return Ok(stream);
+    }
+    let client = CLIENT.get().with_context(|| "服务没有启动")?;
+    let stream = play::resolve_stream(client, id).await?;
+    println!(" > 解析 : {} {}", id, stream.title);
+    STREAMS
+        .lock()
+        .unwrap()
+        .insert(id.to_owned(), (Instant::now(), stream.clone()));
+    Ok(stream)
+}
+
+/// 查询参数中的清晰度, 没有指定时使用配置的清晰度
+/// 指定的清晰度不正确或者不可用时返回BadRequest
+fn query_quality(req: &Request<Body>, key: &str, ids: &[i64]) -> crate::Result<Option<i64>> {
+    let query = req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|x| x.strip_prefix("quality="));
+    match query {
+        None => Ok(down::default_quality(key, ids)),
+        Some(value) => match value.parse::<i64>() {
+            Ok(quality) if ids.contains(&quality) => Ok(Some(quality)),
+            _ => Err(BadRequest(format!(
+                "不可用的清晰度 : {}, 可以使用 : {}",
+                value,
+                ids.iter().join(" / ")
+            ))
+            .into()),
+        },
+    }
+}
+
+/// 选择的视频和音频的地址, ?quality= 在 /audio 中为音频质量, 其他时候为视频的清晰度
+fn stream_urls(
+    req: &Request<Body>,
+    route: &str,
+    stream: &Stream,
+) -> crate::Result<(String, String)> {
+    let video_ids = stream.video.iter().map(|x| x.0).collect_vec();
+    let audio_ids = stream.audio.iter().map(|x| x.0).collect_vec();
+    let (video_quality, audio_quality) = if route == "audio" {
+        (
+            down::default_quality("video_quality", &video_ids),
+            query_quality(req, "audio_quality", &audio_ids)?,
+        )
+    } else {
+        (
+            query_quality(req, "video_quality", &video_ids)?,
+            down::default_quality("audio_quality", &audio_ids),
+        )
+    };
+    let video = video_quality
+        .and_then(|quality| play::stream_url(&stream.video, quality))
+        .with_context(|| "未找到视频")?;
+    let audio = audio_quality
+        .and_then(|quality| play::stream_url(&stream.audio, quality))
+        .with_context(|| "未找到音频")?;
+    Ok((video, audio))
+}
+
+/// 视频的标题, 可以使用的清晰度和播放地址
+async fn info(req: &Request<Body>, id: &str) -> crate::Result<Response<Body>> {
+    let stream = cached_stream(id).await?;
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("127.0.0.1:8090");
+    let body = serde_json::json!({
+        "title": stream.title,
+        "bvid": stream.bvid,
+        "cid": stream.cid,
+        "video": stream
+            .video
+            .iter()
+            .map(|x| serde_json::json!({"quality": x.0, "name": down::video_name(x.0)}))
+            .collect_vec(),
+        "audio": stream.audio.iter().map(|x| x.0).collect_vec(),
+        "urls": {
+            "video": format!("http://{}/video/{}", host, id),
+            "audio": format!("http://{}/audio/{}", host, id),
+            "mux": format!("http://{}/mux/{}", host, id),
+        },
+    });
+    let mut rsp = Response::new(Body::from(body.to_string()));
+    rsp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    Ok(rsp)
+}
+
+/// 带上referer请求CDN, 和下载时的断点续传一样转发Range
+async fn proxy(req: &Request<Body>, route: &str, id: &str) -> crate::Result<Response<Body>> {
+    let stream = cached_stream(id).await?;
+    let (video, audio) = stream_urls(req, route, &stream)?;
+    let url = if route == "video" { video } else { audio };
+    let method = if req.method() == Method::HEAD {
+        reqwest::Method::HEAD
+    } else {
+        reqwest::Method::GET
+    };
+    let mut upstream = http::http_client()
+        .request(method, url.as_str())
+        .header("referer", REFERER);
+    if let Some(range) = req.headers().get(RANGE) {
+        upstream = upstream.header("Range", range.as_bytes());
+    }
+    let rsp = upstream.send().await?;
+    if rsp.status().is_client_error() && rsp.status().as_u16() != 416 {
+        // 地址可能已经过期, 下一次请求时重新解析
+        STREAMS.lock().unwrap().remove(id);
+    }
+    let mut builder = Response::builder().status(rsp.status().as_u16());
+    for name in PROXY_HEADERS {
+        if let Some(value) = rsp.headers().get(name) {
+            builder = builder.header(name, value.as_bytes());
+        }
+    }
+    Ok(builder.body(Body::wrap_stream(rsp.bytes_stream()))?)
+}
+
+/// 使用ffmpeg实时合并视频和音频, 输出分段的MP4, 播放器断开时结束ffmpeg
+async fn mux(req: &Request<Body>, id: &str) -> crate::Result<Response<Body>> {
+    if !*FFMPEG_AVAILABLE {
+        return Err(anyhow::Error::msg(
+            "实时合并需要ffmpeg, 请先安装ffmpeg, 或者检查 ffmpeg_path 配置",
+        ));
+    }
+    let stream = cached_stream(id).await?;
+    let (video, audio) = stream_urls(req, "mux", &stream)?;
+    let builder = Response::builder().header(CONTENT_TYPE, "video/mp4");
+    if req.method() == Method::HEAD {
+        return Ok(builder.body(Body::empty())?);
+    }
+    let user_agent = http::user_agent();
+    let headers = format!("Referer: {}\r\n", REFERER);
+    let mut args = vec!["-loglevel", "error"];
+    for url in [video.as_str(), audio.as_str()] {
+        args.extend([
+            "-user_agent",
+            user_agent.as_str(),
+            "-headers",
+            headers.as_str(),
+            "-i",
+            url,
+        ]);
+    }
+    args.extend([
+        "-map",
+        "0:v:0",
+        "-map",
+        "1:a:0",
+        "-c",
+        "copy",
+        "-f",
+        "mp4",
+        "-movflags",
+        "frag_keyframe+empty_moov+default_base_moof",
+        "pipe:1",
+    ]);
+    let mut child = Command::new(config::ffmpeg_path())
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| "未能启动ffmpeg")?;
+    let mut stdout = child.stdout.take().unwrap();
+    let (mut sender, body) = Body::channel();
+    std::thread::spawn(move || {
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let read = match stdout.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let data = hyper::body::Bytes::copy_from_slice(&buf[..read]);
+            if futures::executor::block_on(sender.send_data(data)).is_err() {
+                break;
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+    Ok(builder.body(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn query_quality_available() {
+        let req = request("/audio/BV1814y1p7Uj?quality=30280");
+        let quality = query_quality(&req, "audio_quality", &[30216, 30280]).unwrap();
+        assert_eq!(quality, Some(30280));
+    }
+
+    #[test]
+    fn query_quality_bad_request() {
+        for uri in [
+            "/video/BV1814y1p7Uj?quality=116",
+            "/video/BV1814y1p7Uj?quality=high",
+        ] {
+            let err = query_quality(&request(uri), "video_quality", &[64, 80]).unwrap_err();
+            assert!(err.is::<BadRequest>());
+        }
+    }
+}
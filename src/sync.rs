@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use chrono::Local;
+use futures::stream::{StreamExt, TryStreamExt};
+
+use crate::api::WebApi;
+use crate::down::{
+    down_dash_archive, file_name, merged_file, output_dir, USER_COLLECTION_DETAIL_PATTERN,
+    USER_FAV_LIST_PATTERN, USER_SPACE_PATTERN,
+};
+use crate::entities::sync_item;
+use crate::local::{
+    allowed_file_name, delete_sync_item, join_paths, load_sync_items, save_sync_item,
+};
+use crate::{app, config, login_client, web_api};
+
+/// 来源中已经删除的视频移动到的文件夹
+const REMOVED_DIR: &str = "已移除";
+
+/// 同步的来源
+pub(crate) struct SyncSource {
+    /// 保存在同步记录中的来源, 例如 fav:123
+    pub id: String,
+    pub name: String,
+    /// (bvid, 标题)
+    pub archives: Vec<(String, String)>,
+}
+
+/// 同步收藏夹或合集
+pub(crate) async fn sync() -> crate::Result<()> {
+    let url = app::url_value();
+    let client = login_client().await?;
+    let source = fetch_source(&client, url.as_str()).await?;
+    sync_source(&client, source, app::sync_removed_value().as_str()).await
+}
+
+/// 读取收藏夹或合集中的全部视频
+pub(crate) async fn fetch_source(client: &WebApi, url: &str) -> crate::Result<SyncSource> {
+    if let Some(find) = USER_COLLECTION_DETAIL_PATTERN.captures(url) {
+        let mid: i64 = find.get(1).unwrap().as_str().parse().unwrap();
+        let sid: i64 = find.get(2).unwrap().as_str().parse().unwrap();
+        return fetch_collection_detail(client, mid, sid).await;
+    }
+    if let Some(find) = USER_FAV_LIST_PATTERN.captures(url) {
+        let fid: i64 = find.get(1).unwrap().as_str().parse().unwrap();
+        return fetch_fav_list(client, fid).await;
+    }
+    if let Some(find) = USER_SPACE_PATTERN.captures(url) {
+        let mid: i64 = find.get(1).unwrap().as_str().parse().unwrap();
+        return fetch_user_videos(mid).await;
+    }
+    Err(anyhow::Error::msg(
+        "只能同步收藏夹, 合集或UP主的投稿 : ".to_owned() + url,
+    ))
+}
+
+pub(crate) async fn fetch_collection_detail(
+    client: &WebApi,
+    mid: i64,
+    sid: i64,
+) -> crate::Result<SyncSource> {
+    let mut current_page = 1;
+    let mut name = String::default();
+    let mut archives = vec![];
+    loop {
+        let page = client
+            .collection_archives(mid, sid, current_page, 20)
+            .await?;
+        if current_page == 1 {
+            name = page.meta.name;
+        }
+        for archive in page.archives {
+            archives.push((archive.bvid, archive.title));
+        }
+        if page.page.page_size * page.page.page_num >= page.page.total {
+            break;
+        }
+        current_page += 1;
+    }
+    Ok(SyncSource {
+        id: format!("collection:{}:{}", mid, sid),
+        name,
+        archives,
+    })
+}
+
+pub(crate) async fn fetch_fav_list(client: &WebApi, fid: i64) -> crate::Result<SyncSource> {
+    let mut current_page = 1;
+    let mut name = String::default();
+    let mut archives = vec![];
+    loop {
+        let page = client.fav_resource_page(fid, current_page, 20).await?;
+        if current_page == 1 {
+            name = page.info.title;
+        }
+        // 失效的视频不能下载, 和来源中已删除的视频一样处理
+        for archive in page.medias.into_iter().filter(|x| !x.is_invalid()) {
+            let title = if archive.page == 0 {
+                format!("{} - {}", archive.title, archive.intro)
+            } else {
+                archive.title
+            };
+            archives.push((archive.bvid, title));
+        }
+        if !page.has_more {
+            break;
+        }
+        current_page += 1;
+    }
+    Ok(SyncSource {
+        id: format!("fav:{}", fid),
+        name,
+        archives,
+    })
+}
+
+pub(crate) async fn fetch_user_videos(mid: i64) -> crate::Result<SyncSource> {
+    let web_api = web_api().await?;
+    let name = web_api.user_card(mid).await?.card.name;
+    let mut current_page = 1;
+    let mut archives = vec![];
+    loop {
+        let page = web_api.space_video_page(mid, current_page, 30).await?;
+        for archive in page.list.vlist {
+            archives.push((archive.bvid, archive.title));
+        }
+        if page.page.ps * page.page.pn >= page.page.count {
+            break;
+        }
+        current_page += 1;
+    }
+    Ok(SyncSource {
+        id: format!("user:{}", mid),
+        name,
+        archives,
+    })
+}
+
+/// 按照同步记录下载新的视频, 标题变化时重命名文件
+/// removed为来源中已经删除的视频的处理方式: keep(保留) / archive(移动到"已移除"文件夹) / delete(删除)
+pub(crate) async fn sync_source(
+    client: &WebApi,
+    source: SyncSource,
+    removed: &str,
+) -> crate::Result<()> {
+    let folder = join_paths(vec![
+        output_dir().as_str(),
+        allowed_file_name(source.name.as_str()).as_str(),
+    ]);
+    std::fs::create_dir_all(folder.as_str())?;
+    let synced: HashMap<String, sync_item::Model> = load_sync_items(source.id.as_str())
+        .await?
+        .into_iter()
+        .map(|item| (item.bvid.clone(), item))
+        .collect();
+    println!();
+    println!(
+        "同步 : {} : 共 {} 个视频, 已同步 {} 个",
+        source.name,
+        source.archives.len(),
+        synced.len()
+    );
+    // 文件属于哪个视频, 不能重命名或者指向其他视频的文件
+    let mut owners: HashMap<String, String> = synced
+        .values()
+        .map(|item| (item.file.clone(), item.bvid.clone()))
+        .collect();
+    let mut new_archives = vec![];
+    for (bvid, title) in &source.archives {
+        let file = merged_file(&folder, &file_name(title, bvid));
+        if let Some(owner) = owners.get(&file).filter(|owner| *owner != bvid) {
+            println!("跳过 : {} : 文件已属于 {} : {}", title, owner, file);
+            continue;
+        }
+        match synced.get(bvid) {
+            Some(item) => {
+                if !Path::new(&file).exists() {
+                    // 只在同一个文件夹中重命名, 修改了输出的文件夹时不移动原来的文件
+                    let same_folder = Path::new(&item.file).parent() == Path::new(&file).parent();
+                    if same_folder && Path::new(&item.file).exists() {
+                        std::fs::rename(&item.file, &file)?;
+                        println!("重命名 : {} -> {}", item.file, file);
+                    } else {
+                        // 文件已被删除或者不在这个文件夹中, 重新下载
+                        owners.insert(file, bvid.clone());
+                        new_archives.push((bvid.clone(), title.clone()));
+                        continue;
+                    }
+                } else if item.file == file && &item.title == title {
+                    continue;
+                }
+                owners.remove(&item.file);
+                owners.insert(file.clone(), bvid.clone());
+                save_sync_item(sync_item::Model {
+                    source: source.id.clone(),
+                    bvid: bvid.clone(),
+                    title: title.clone(),
+                    file,
+                    synced_at: Local::now().timestamp(),
+                })
+                .await?;
+            }
+            None => {
+                owners.insert(file, bvid.clone());
+                new_archives.push((bvid.clone(), title.clone()));
+            }
+        }
+    }
+    // 下载新的视频, 下载成功后写入同步记录
+    let new_count = new_archives.len();
+    let source_id = source.id.as_str();
+    let folder_str = folder.as_str();
+    futures::stream::iter(new_archives.into_iter().map(|(bvid, title)| async move {
+        println!();
+        println!("{}", title);
+        let name = file_name(&title, &bvid);
+        down_dash_archive(client, bvid.clone(), None, folder_str, &name).await?;
+        save_sync_item(sync_item::Model {
+            source: source_id.to_owned(),
+            bvid,
+            title,
+            file: merged_file(folder_str, &name),
+            synced_at: Local::now().timestamp(),
+        })
+        .await
+    }))
+    .buffer_unordered(config::concurrency())
+    .try_collect::<Vec<()>>()
+    .await?;
+    // 来源中已经删除的视频
+    let remote: HashSet<&String> = source.archives.iter().map(|(bvid, _)| bvid).collect();
+    for item in synced.values().filter(|item| !remote.contains(&item.bvid)) {
+        match removed {
+            "archive" => {
+                let removed_dir = join_paths(vec![folder.as_str(), REMOVED_DIR]);
+                std::fs::create_dir_all(removed_dir.as_str())?;
+                if let Some(file_name) = Path::new(&item.file).file_name() {
+                    if Path::new(&item.file).exists() {
+                        std::fs::rename(&item.file, Path::new(&removed_dir).join(file_name))?;
+                    }
+                }
+                delete_sync_item(&item.source, &item.bvid).await?;
+                println!("已移动到{} : {}", REMOVED_DIR, item.title);
+            }
+            "delete" => {
+                if Path::new(&item.file).exists() {
+                    std::fs::remove_file(&item.file)?;
+                }
+                delete_sync_item(&item.source, &item.bvid).await?;
+                println!("已删除 : {}", item.title);
+            }
+            _ => println!("来源中已删除 : {} ({})", item.title, item.file),
+        }
+    }
+    println!();
+    println!("同步完成 : {} : 新下载 {} 个", source.name, new_count);
+    Ok(())
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use bilirust::WebToken;
+use chrono::{Local, TimeZone};
+use num_bigint::BigUint;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string};
+use sha2::{Digest, Sha256};
+
+use crate::api::WebApi;
+use crate::local::{load_property, save_property};
+use crate::profile;
+
+/// 刷新cookie时用于生成CorrespondPath的RSA公钥 (1024位, e = 65537)
+const CORRESPOND_KEY_MODULUS: &str = "\
+    cb81dd8e02470656da04dd38544446e2a3412051cfe9adc6a330a5ef90228509\
+    684960970b91c3360ca29c49e1690ff8fa068cb9dfc6179d1e9585cb9424e847\
+    db1ef59f33e37dd4dca8ccfb7631ee9b4a92640d00c8204300152a0ab7cd8028\
+    89d3445aec69918fe6022b534912e7b095be3424dad1ba81145e969b533181f1";
+const CORRESPOND_KEY_EXPONENT: u32 = 65537;
+
+/// 剩余有效期少于这个时间(秒)时尝试刷新
+const REFRESH_BEFORE_EXPIRES: i64 = 7 * 24 * 60 * 60;
+
+/// WebToken之外刷新时需要用到的信息
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TokenMeta {
+    pub bili_jct: String,
+    pub refresh_token: String,
+}
+
+/// 登录状态
+pub(crate) struct TokenState {
+    pub is_login: bool,
+    pub expires: Option<i64>,
+    pub need_refresh: bool,
+}
+
+impl TokenState {
+    /// 剩余有效期的描述
+    pub(crate) fn expires_description(&self) -> String {
+        match self.expires {
+            Some(expires) => {
+                let remaining = expires - Local::now().timestamp();
+                let date = Local
+                    .timestamp_opt(expires, 0)
+                    .single()
+                    .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                if remaining > 0 {
+                    format!("{} (剩余 {} 天)", date, remaining / 86400)
+                } else {
+                    format!("{} (已过期)", date)
+                }
+            }
+            None => "未知".to_owned(),
+        }
+    }
+}
+
+/// 账号的token信息在配置表中的key, 其中有refresh_token和bili_jct
+pub(crate) fn token_meta_key(profile: &str) -> String {
+    profile::profile_key("token_meta", profile)
+}
+
+pub(crate) async fn load_token_meta(profile: &str) -> crate::Result<TokenMeta> {
+    let property = load_property(token_meta_key(profile)).await?;
+    if property.is_empty() {
+        return Ok(TokenMeta::default());
+    }
+    Ok(from_str(property.as_str())?)
+}
+
+pub(crate) async fn save_token_meta(profile: &str, meta: &TokenMeta) -> crate::Result<()> {
+    save_property(token_meta_key(profile), to_string(meta)?).await
+}
+
+pub(crate) async fn save_web_token(profile: &str, token: &WebToken) -> crate::Result<()> {
+    save_property(profile::web_token_key(profile), to_string(token)?).await
+}
+
+/// SESSDATA的格式为 `xxx%2C{过期时间}%2Cxxx`
+pub(crate) fn sess_data_expires(sess_data: &str) -> Option<i64> {
+    sess_data
+        .replace("%2C", ",")
+        .replace("%2c", ",")
+        .split(',')
+        .nth(1)
+        .and_then(|expires| expires.parse().ok())
+}
+
+/// 读取url中的参数
+pub(crate) fn query_params(url: &str) -> HashMap<String, String> {
+    match reqwest::Url::parse(url) {
+        Ok(url) => url.query_pairs().into_owned().collect(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// 读取浏览器导出的cookie, 支持Netscape格式的cookies.txt和JSON数组格式
+pub(crate) fn parse_cookies(content: &str) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    if content.trim_start().starts_with('[') {
+        if let Ok(serde_json::Value::Array(list)) = from_str(content) {
+            for cookie in list {
+                let domain = cookie["domain"].as_str().unwrap_or_default();
+                if !domain.ends_with("bilibili.com") {
+                    continue;
+                }
+                if let (Some(name), Some(value)) =
+                    (cookie["name"].as_str(), cookie["value"].as_str())
+                {
+                    cookies.insert(name.to_owned(), value.to_owned());
+                }
+            }
+        }
+        return cookies;
+    }
+    for line in content.lines() {
+        // HttpOnly的cookie以 #HttpOnly_ 开头, 其他以#开头的是注释
+        let line = line.trim_start_matches("#HttpOnly_");
+        if line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 || !fields[0].ends_with("bilibili.com") {
+            continue;
+        }
+        cookies.insert(fields[5].to_owned(), fields[6].trim().to_owned());
+    }
+    cookies
+}
+
+/// 使用cookie生成WebToken, 和扫码登录成功时返回的url格式一致
+pub(crate) fn web_token_from_cookies(cookies: &HashMap<String, String>) -> crate::Result<WebToken> {
+    let sess_data = cookies
+        .get("SESSDATA")
+        .with_context(|| "缺少SESSDATA")?
+        .as_str();
+    let cookie = |name: &str| cookies.get(name).cloned().unwrap_or_default();
+    let url = format!(
+        "https://passport.biligame.com/crossDomain?DedeUserID={}&DedeUserID__ckMd5={}&Expires={}&SESSDATA={}&bili_jct={}&gourl=https%3A%2F%2Fwww.bilibili.com",
+        cookie("DedeUserID"),
+        cookie("DedeUserID__ckMd5"),
+        sess_data_expires(sess_data).unwrap_or_default(),
+        sess_data,
+        cookie("bili_jct"),
+    );
+    bilirust::Client::new().login_qr_info_parse_token(url)
+}
+
+/// 检查登录状态和是否需要刷新
+pub(crate) async fn token_state(token: &WebToken, meta: &TokenMeta) -> crate::Result<TokenState> {
+    let mut web_api = WebApi::new();
+    web_api.set_sess_data(token.sessdata.clone());
+    let is_login = web_api.nav().await?.is_login;
+    let expires = sess_data_expires(token.sessdata.as_str());
+    let mut need_refresh = match expires {
+        Some(expires) => expires - Local::now().timestamp() < REFRESH_BEFORE_EXPIRES,
+        None => false,
+    };
+    if is_login && !need_refresh && !meta.bili_jct.is_empty() {
+        need_refresh = web_api.cookie_info(meta.bili_jct.as_str()).await?.refresh;
+    }
+    Ok(TokenState {
+        is_login,
+        expires,
+        need_refresh,
+    })
+}
+
+/// 使用refresh_token刷新cookie
+pub(crate) async fn refresh_token(
+    token: &WebToken,
+    meta: &TokenMeta,
+) -> crate::Result<(WebToken, TokenMeta)> {
+    if meta.refresh_token.is_empty() || meta.bili_jct.is_empty() {
+        return Err(anyhow::Error::msg("没有保存refresh_token, 无法自动刷新"));
+    }
+    let mut web_api = WebApi::new();
+    web_api.set_sess_data(token.sessdata.clone());
+    let correspond_path = correspond_path(Local::now().timestamp_millis())?;
+    let refresh_csrf = web_api.refresh_csrf(correspond_path.as_str()).await?;
+    let (refresh, cookies) = web_api
+        .cookie_refresh(
+            meta.bili_jct.as_str(),
+            refresh_csrf.as_str(),
+            meta.refresh_token.as_str(),
+        )
+        .await?;
+    let new_token = web_token_from_cookies(&cookies)?;
+    let new_meta = TokenMeta {
+        bili_jct: cookies.get("bili_jct").cloned().unwrap_or_default(),
+        refresh_token: refresh.refresh_token,
+    };
+    let mut web_api = WebApi::new();
+    web_api.set_sess_data(new_token.sessdata.clone());
+    web_api
+        .confirm_refresh(new_meta.bili_jct.as_str(), meta.refresh_token.as_str())
+        .await?;
+    Ok((new_token, new_meta))
+}
+
+fn correspond_path(timestamp: i64) -> crate::Result<String> {
+    let modulus = BigUint::parse_bytes(CORRESPOND_KEY_MODULUS.as_bytes(), 16)
+        .with_context(|| "公钥格式错误")?;
+    let encrypted = rsa_oaep_sha256_encrypt(
+        &modulus,
+        &BigUint::from(CORRESPOND_KEY_EXPONENT),
+        format!("refresh_{}", timestamp).as_bytes(),
+    )?;
+    Ok(hex::encode(encrypted))
+}
+
+/// RSAES-OAEP加密 (RFC 8017), 哈希和MGF1都使用SHA-256, label为空
+fn rsa_oaep_sha256_encrypt(
+    modulus: &BigUint,
+    exponent: &BigUint,
+    message: &[u8],
+) -> crate::Result<Vec<u8>> {
+    let mut seed = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut seed);
+    rsa_oaep_sha256_encrypt_with_seed(modulus, exponent, message, &seed)
+}
+
+/// 使用指定的seed加密, 相同的输入得到相同的结果
+fn rsa_oaep_sha256_encrypt_with_seed(
+    modulus: &BigUint,
+    exponent: &BigUint,
+    message: &[u8],
+    seed: &[u8; 32],
+) -> crate::Result<Vec<u8>> {
+    let k = modulus.bits().div_ceil(8) as usize;
+    let h_len = seed.len();
+    if message.len() + 2 * h_len + 2 > k {
+        return Err(anyhow::Error::msg("加密的内容过长"));
+    }
+    // DB = lHash || PS || 0x01 || M
+    let mut db = Sha256::digest(b"").to_vec();
+    db.resize(k - message.len() - h_len - 2, 0);
+    db.push(1);
+    db.extend_from_slice(message);
+    let mut seed = seed.to_vec();
+    for (b, m) in db.iter_mut().zip(mgf1_sha256(&seed, k - h_len - 1)) {
+        *b ^= m;
+    }
+    for (b, m) in seed.iter_mut().zip(mgf1_sha256(&db, h_len)) {
+        *b ^= m;
+    }
+    // EM = 0x00 || maskedSeed || maskedDB
+    let mut em = vec![0u8];
+    em.extend_from_slice(&seed);
+    em.extend_from_slice(&db);
+    let c = BigUint::from_bytes_be(&em)
+        .modpow(exponent, modulus)
+        .to_bytes_be();
+    let mut encrypted = vec![0u8; k - c.len()];
+    encrypted.extend_from_slice(&c);
+    Ok(encrypted)
+}
+
+fn mgf1_sha256(seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = vec![];
+    let mut counter: u32 = 0;
+    while mask.len() < len {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        hasher.update(counter.to_be_bytes());
+        mask.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用的公钥, 对应的私钥只用于生成下面的密文
+    const TEST_MODULUS: &str = "\
+        f7a7f0f947f8dce71d4cf9ca88d7fa16866ba5b54a289673671860e9cab8f3d4\
+        466cc912372cd4e0f5a1e1111b4266aef65a934ce8ccb1b3f0745490c7347eca\
+        138baae7591519cf6f4b5458725b41df6160e296c46073d053029d9e76c74bb0\
+        55a9faf71b26b0d18b30e8b37423712c9c37ef85025370195f3acd9b3d213359";
+
+    /// 使用OpenSSL的RSA-OAEP(SHA-256)解密验证过的密文, seed为 00 01 .. 1f
+    const TEST_CIPHERTEXT: &str = "\
+        aed997e51fb953236359ca46756e07259eb956a24c490a5f7d9ee585f96bde4d\
+        d957978002af262f73c9cc363d9fb15d383bde0828a440802f34eeceaff3468e\
+        505f9d68e021ac079258a49aaf5f2fd84fa40d0db02e9e9f647682b5bec61009\
+        d2bda974c5ea483626f0f785371c8382fe7cc0570c4964a5a7edd35a5bd130fd";
+
+    #[test]
+    fn oaep_known_answer() {
+        let modulus = BigUint::parse_bytes(TEST_MODULUS.as_bytes(), 16).unwrap();
+        let seed: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let encrypted = rsa_oaep_sha256_encrypt_with_seed(
+            &modulus,
+            &BigUint::from(65537u32),
+            b"refresh_1700000000000",
+            &seed,
+        )
+        .unwrap();
+        assert_eq!(hex::encode(encrypted), TEST_CIPHERTEXT);
+    }
+
+    #[test]
+    fn oaep_message_too_long() {
+        let modulus = BigUint::parse_bytes(TEST_MODULUS.as_bytes(), 16).unwrap();
+        // 1024位的公钥最多加密 128 - 2 * 32 - 2 = 62 字节
+        let exponent = BigUint::from(65537u32);
+        assert!(rsa_oaep_sha256_encrypt(&modulus, &exponent, &[0; 62]).is_ok());
+        assert!(rsa_oaep_sha256_encrypt(&modulus, &exponent, &[0; 63]).is_err());
+    }
+
+    #[test]
+    fn correspond_path_length() {
+        assert_eq!(correspond_path(1700000000000).unwrap().len(), 256);
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Deserialize;
+
+use crate::chapter::Chapter;
+use crate::down::print_line;
+use crate::ffmpeg::{run_ffmpeg, FfmpegMuxer};
+use crate::mux::{self, Muxer};
+use crate::{app, config};
+
+/// 配置文件中转码配置的表
+const PROFILES_TABLE: &str = "transcode_profiles";
+
+/// 转码的配置, 可以在配置文件中使用 [transcode_profiles.名称] 添加或者覆盖内置的配置
+/// 配置文件中的 transcode 是使用的配置名称, 所以配置放在另一个表中
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TranscodeProfile {
+    /// ffmpeg的视频编码器, 例如 libx264, copy为不转码
+    video_codec: String,
+    /// ffmpeg的音频编码器, 例如 aac, copy为不转码
+    audio_codec: String,
+    /// 最大高度, 超过时按照比例缩小, 0为不缩放
+    height: u32,
+    crf: Option<u32>,
+    preset: String,
+    audio_bitrate: String,
+    /// 额外的ffmpeg参数
+    args: Vec<String>,
+}
+
+/// 内置的转码配置, 只使用软件编码器, 不依赖显卡
+fn builtin_profiles() -> Vec<(&'static str, TranscodeProfile)> {
+    vec![
+        (
+            // 兼容旧电视和网页播放器
+            "h264-1080p",
+            TranscodeProfile {
+                video_codec: "libx264".to_owned(),
+                audio_codec: "aac".to_owned(),
+                height: 1080,
+                crf: Some(20),
+                preset: "medium".to_owned(),
+                audio_bitrate: "192k".to_owned(),
+                args: vec!["-pix_fmt", "yuv420p", "-profile:v", "high"]
+                    .into_iter()
+                    .map(|x| x.to_owned())
+                    .collect(),
+            },
+        ),
+        (
+            "hevc-small",
+            TranscodeProfile {
+                video_codec: "libx265".to_owned(),
+                audio_codec: "aac".to_owned(),
+                height: 720,
+                crf: Some(28),
+                preset: "medium".to_owned(),
+                audio_bitrate: "128k".to_owned(),
+                args: vec!["-tag:v", "hvc1"]
+                    .into_iter()
+                    .map(|x| x.to_owned())
+                    .collect(),
+            },
+        ),
+        (
+            "av1-archive",
+            TranscodeProfile {
+                video_codec: "libsvtav1".to_owned(),
+                audio_codec: "copy".to_owned(),
+                height: 0,
+                crf: Some(30),
+                preset: "6".to_owned(),
+                audio_bitrate: String::default(),
+                args: vec!["-pix_fmt", "yuv420p10le"]
+                    .into_iter()
+                    .map(|x| x.to_owned())
+                    .collect(),
+            },
+        ),
+    ]
+}
+
+/// 全部的转码配置名称
+fn profile_names() -> Vec<String> {
+    let mut names: Vec<String> = builtin_profiles()
+        .into_iter()
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    if let Some(table) = config::config_table(PROFILES_TABLE) {
+        for name in table.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+/// 按照名称读取转码配置, 配置文件中的配置优先于内置的配置
+fn find_profile(name: &str) -> crate::Result<TranscodeProfile> {
+    if let Some(value) =
+        config::config_table(PROFILES_TABLE).and_then(|table| table.get(name).cloned())
+    {
+        let profile: TranscodeProfile = value.try_into().map_err(|err| {
+            anyhow::Error::msg(format!("配置文件中的转码配置错误 : {} : {}", name, err))
+        })?;
+        if profile.video_codec.is_empty() || profile.audio_codec.is_empty() {
+            return Err(anyhow::Error::msg(format!(
+                "配置文件中的转码配置错误 : {} : 需要 video_codec 和 audio_codec",
+                name
+            )));
+        }
+        return Ok(profile);
+    }
+    match builtin_profiles().into_iter().find(|(x, _)| *x == name) {
+        Some((_, profile)) => Ok(profile),
+        None => Err(anyhow::Error::msg(format!(
+            "未知的转码配置 : {}, 可以使用的配置 : {}",
+            name,
+            profile_names().join(" / ")
+        ))),
+    }
+}
+
+/// 使用的转码配置: --transcode > 配置中的transcode, 都没有时不转码
+fn selected_profile() -> crate::Result<Option<(String, TranscodeProfile)>> {
+    match app::transcode_value().or_else(|| config::config_value_opt("transcode")) {
+        Some(name) => Ok(Some((name.clone(), find_profile(name.as_str())?))),
+        None => Ok(None),
+    }
+}
+
+/// 在下载之前检查转码配置和ffmpeg
+/// 转码总是运行ffmpeg命令行, 使用ffmpeg_api构建时也需要安装ffmpeg
+pub(crate) fn init_transcode() -> crate::Result<()> {
+    if selected_profile()?.is_some() && !FfmpegMuxer.available() {
+        return Err(anyhow::Error::msg(
+            "转码需要ffmpeg命令行 (使用ffmpeg_api构建时也需要), 请先安装ffmpeg, 或者检查 ffmpeg_path 配置",
+        ));
+    }
+    Ok(())
+}
+
+/// 合并音频和视频, 选择了转码配置时合并之后转码, 转码时ffmpeg会保留章节
+/// 转码完成之前不会出现output, 中断后再次下载时不会跳过
+pub(crate) fn merge_file(
+    video: &str,
+    audio: &str,
+    chapters: &[Chapter],
+    output: &str,
+) -> crate::Result<()> {
+    match selected_profile()? {
+        Some((name, profile)) => {
+            let merged = temp_file(output, "merged");
+            mux::merge_file(video, audio, chapters, merged.as_str())?;
+            print_line(format!(" > 转码 ({})", name));
+            transcode(&name, &profile, merged.as_str(), output)?;
+            let _ = std::fs::remove_file(merged.as_str());
+            Ok(())
+        }
+        None => mux::merge_file(video, audio, chapters, output),
+    }
+}
+
+/// 选择了转码配置时转码下载好的文件, 转码后替换原来的文件
+pub(crate) fn transcode_in_place(file: &str) -> crate::Result<()> {
+    if let Some((name, profile)) = selected_profile()? {
+        print_line(format!(" > 转码 ({})", name));
+        let output = temp_file(file, "transcode");
+        transcode(&name, &profile, file, output.as_str())?;
+        std::fs::rename(output.as_str(), file)?;
+    }
+    Ok(())
+}
+
+/// 和output在同一个文件夹的临时文件
+fn temp_file(output: &str, tag: &str) -> String {
+    format!("{}.{}.mp4", output.trim_end_matches(".mp4"), tag)
+}
+
+fn transcode(
+    name: &str,
+    profile: &TranscodeProfile,
+    input: &str,
+    output: &str,
+) -> crate::Result<()> {
+    let mut args: Vec<String> = vec![];
+    let mut arg = |values: &[&str]| args.extend(values.iter().map(|x| x.to_string()));
+    arg(&["-y", "-i", input, "-map", "0:v:0", "-map", "0:a:0?"]);
+    arg(&["-c:v", profile.video_codec.as_str()]);
+    if profile.video_codec != "copy" {
+        if let Some(crf) = profile.crf {
+            arg(&["-crf", crf.to_string().as_str()]);
+        }
+        if !profile.preset.is_empty() {
+            arg(&["-preset", profile.preset.as_str()]);
+        }
+        if profile.height > 0 {
+            // 不放大, 宽度保持比例并且为偶数
+            arg(&[
+                "-vf",
+                format!("scale=-2:'min({},ih)'", profile.height).as_str(),
+            ]);
+        }
+    }
+    arg(&["-c:a", profile.audio_codec.as_str()]);
+    if profile.audio_codec != "copy" && !profile.audio_bitrate.is_empty() {
+        arg(&["-b:a", profile.audio_bitrate.as_str()]);
+    }
+    args.extend(profile.args.iter().cloned());
+    args.extend(
+        ["-movflags", "+faststart", output]
+            .iter()
+            .map(|x| x.to_string()),
+    );
+    let args: Vec<&str> = args.iter().map(|x| x.as_str()).collect();
+    run_ffmpeg(&args, format!("转码 ({})", name).as_str()).inspect_err(|_| {
+        let _ = std::fs::remove_file(output);
+    })
+}
@@ -0,0 +1,749 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::io::Stdout;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use futures::FutureExt;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Span, Spans};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs};
+use ratatui::{Frame, Terminal};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+
+use crate::api::WebApi;
+use crate::{checked_login_client, down, login_client, sync};
+
+/// 顶部的分类
+const TABS: [&str; 4] = ["收藏夹", "稍后再看", "关注的UP主", "追番"];
+
+/// 列表中的内容
+#[derive(Clone, PartialEq)]
+enum Target {
+    /// (UP主, 收藏夹)
+    FavFolder(i64, i64),
+    Uploader(i64),
+    Video(String),
+    Season(i64),
+}
+
+impl Target {
+    /// 下载时使用的url, UP主需要打开后选择视频
+    fn url(&self) -> Option<String> {
+        match self {
+            Target::FavFolder(mid, fid) => Some(format!(
+                "https://space.bilibili.com/{}/favlist?fid={}",
+                mid, fid
+            )),
+            Target::Uploader(_) => None,
+            Target::Video(bvid) => Some(format!("https://www.bilibili.com/video/{}", bvid)),
+            Target::Season(season_id) => Some(format!(
+                "https://www.bilibili.com/bangumi/play/ss{}",
+                season_id
+            )),
+        }
+    }
+
+    /// 回车时打开, 而不是下载
+    fn can_open(&self) -> bool {
+        matches!(self, Target::FavFolder(_, _) | Target::Uploader(_))
+    }
+}
+
+struct Entry {
+    title: String,
+    detail: String,
+    target: Target,
+}
+
+/// 一个列表, 打开收藏夹或UP主时会在当前分类中压入新的列表
+struct View {
+    title: String,
+    /// 打开的内容, 分类的第一个列表为None
+    source: Option<Target>,
+    entries: Vec<Entry>,
+    /// 选中的序号
+    selected: HashSet<usize>,
+    /// 搜索的关键字
+    filter: String,
+    /// 光标在显示的内容中的位置
+    state: ListState,
+}
+
+impl View {
+    fn new(title: String, source: Option<Target>, entries: Vec<Entry>) -> Self {
+        let mut state = ListState::default();
+        if !entries.is_empty() {
+            state.select(Some(0));
+        }
+        View {
+            title,
+            source,
+            entries,
+            selected: HashSet::new(),
+            filter: String::default(),
+            state,
+        }
+    }
+
+    /// 符合搜索关键字的内容的序号
+    fn visible(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                filter.is_empty()
+                    || entry.title.to_lowercase().contains(filter.as_str())
+                    || entry.detail.to_lowercase().contains(filter.as_str())
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// 光标所在的内容的序号
+    fn current(&self) -> Option<usize> {
+        let visible = self.visible();
+        self.state
+            .selected()
+            .and_then(|position| visible.get(position).copied())
+    }
+
+    fn move_cursor(&mut self, offset: i64) {
+        let len = self.visible().len() as i64;
+        if len == 0 {
+            self.state.select(None);
+            return;
+        }
+        let position = self.state.selected().unwrap_or(0) as i64 + offset;
+        self.state.select(Some(position.clamp(0, len - 1) as usize));
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.state.select(if self.visible().is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+}
+
+/// 下载队列中的状态
+enum QueueStatus {
+    Waiting,
+    /// 下载中, 以及下载最后输出的一行
+    Running(String),
+    Done,
+    Failed(String),
+}
+
+struct QueueItem {
+    title: String,
+    target: Target,
+    status: QueueStatus,
+}
+
+/// 下载队列, 在后台的任务中按照顺序下载, 下载的输出通过channel显示在队列中
+struct DownloadQueue {
+    items: Arc<Mutex<Vec<QueueItem>>>,
+    sender: UnboundedSender<usize>,
+    /// 下载的任务, 退出时结束
+    worker: JoinHandle<()>,
+}
+
+impl DownloadQueue {
+    fn new() -> Self {
+        let items = Arc::new(Mutex::new(Vec::<QueueItem>::new()));
+        let (sender, mut receiver) = unbounded_channel::<usize>();
+        let (progress_sender, mut progress) = unbounded_channel::<String>();
+        down::set_progress(progress_sender);
+        let worker_items = items.clone();
+        let worker = tokio::spawn(async move {
+            while let Some(index) = receiver.recv().await {
+                let (title, target) = {
+                    let mut items = worker_items.lock().unwrap();
+                    items[index].status = QueueStatus::Running(String::default());
+                    (items[index].title.clone(), items[index].target.clone())
+                };
+                // 下载中的panic显示为失败, 不影响队列中的其他下载
+                let download = AssertUnwindSafe(download(title, target)).catch_unwind();
+                tokio::pin!(download);
+                let result = loop {
+                    tokio::select! {
+                        result = &mut download => break result,
+                        Some(line) = progress.recv() => {
+                            worker_items.lock().unwrap()[index].status = QueueStatus::Running(line);
+                        }
+                    }
+                };
+                worker_items.lock().unwrap()[index].status = match result {
+                    Ok(Ok(_)) => QueueStatus::Done,
+                    Ok(Err(err)) => QueueStatus::Failed(err.to_string()),
+                    Err(panic) => QueueStatus::Failed(panic_message(panic)),
+                };
+            }
+        });
+        DownloadQueue {
+            items,
+            sender,
+            worker,
+        }
+    }
+
+    /// 加入队列, 已经在队列中并且没有失败的会被跳过, 返回加入的数量
+    fn push(&self, downloads: Vec<(String, Target)>) -> usize {
+        let mut items = self.items.lock().unwrap();
+        let mut count = 0;
+        for (title, target) in downloads {
+            if items
+                .iter()
+                .any(|item| item.target == target && !matches!(item.status, QueueStatus::Failed(_)))
+            {
+                continue;
+            }
+            items.push(QueueItem {
+                title,
+                target,
+                status: QueueStatus::Waiting,
+            });
+            let _ = self.sender.send(items.len() - 1);
+            count += 1;
+        }
+        count
+    }
+
+    /// 没有完成的数量
+    fn unfinished(&self) -> usize {
+        self.items
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|item| matches!(item.status, QueueStatus::Waiting | QueueStatus::Running(_)))
+            .count()
+    }
+
+    /// 结束正在运行的下载
+    fn stop(&self) {
+        self.worker.abort();
+    }
+}
+
+/// 在当前进程中下载, 和使用 down 命令的结果相同
+/// 界面中不能交互, 视频总是下载DASH, 需要选择的清晰度使用最高的清晰度
+async fn download(title: String, target: Target) -> crate::Result<()> {
+    match target {
+        Target::Video(bvid) => {
+            let client = checked_login_client(false).await?;
+            let name = down::file_name(&title, &bvid);
+            down::down_dash_archive(&client, bvid, None, &down::output_dir(), &name).await
+        }
+        target => match target.url() {
+            Some(url) => down::down_url(url).await,
+            None => Err(anyhow::Error::msg("UP主需要打开后选择视频")),
+        },
+    }
+}
+
+/// panic的信息
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "panic".to_owned(),
+        },
+    }
+}
+
+/// 进入全屏界面, 退出或者出错时通过Drop恢复终端
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> crate::Result<Self> {
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        // 界面中的panic先恢复终端再输出, 下载任务中的panic显示在下载队列中
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if std::thread::current().name() == Some("main") {
+                restore_terminal();
+                default_hook(info);
+            }
+        }));
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = std::panic::take_hook();
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(std::io::stdout(), LeaveAlternateScreen, Show);
+}
+
+/// 按键的结果
+enum Action {
+    None,
+    Quit,
+    Open(Target, String),
+    Reload,
+}
+
+struct App {
+    tab: usize,
+    /// 每个分类中打开的列表
+    views: Vec<Vec<View>>,
+    /// 正在输入搜索的关键字
+    searching: bool,
+    message: String,
+    /// 还有没完成的下载时, 需要再按一次q退出
+    quit_confirm: bool,
+    queue: DownloadQueue,
+}
+
+impl App {
+    fn view(&mut self) -> Option<&mut View> {
+        self.views[self.tab].last_mut()
+    }
+
+    /// 把选中的内容加入下载队列, 没有选中时使用光标所在的内容
+    fn enqueue(&mut self) {
+        let Some(view) = self.views[self.tab].last_mut() else {
+            return;
+        };
+        let mut indexes = view.selected.iter().copied().collect::<Vec<usize>>();
+        indexes.sort();
+        if indexes.is_empty() {
+            indexes.extend(view.current());
+        }
+        let downloads = indexes
+            .iter()
+            .filter_map(|index| {
+                let entry = &view.entries[*index];
+                entry
+                    .target
+                    .url()
+                    .map(|_| (entry.title.clone(), entry.target.clone()))
+            })
+            .collect::<Vec<(String, Target)>>();
+        view.selected.clear();
+        if downloads.is_empty() {
+            self.message = "没有可以下载的内容, UP主需要按回车打开后选择视频".to_owned();
+            return;
+        }
+        let count = self.queue.push(downloads);
+        self.message = format!("已加入下载队列 : {} 个", count);
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        if self.searching {
+            if let Some(view) = self.view() {
+                match key.code {
+                    KeyCode::Char(char) => {
+                        let filter = format!("{}{}", view.filter, char);
+                        view.set_filter(filter);
+                    }
+                    KeyCode::Backspace => {
+                        let mut filter = view.filter.clone();
+                        filter.pop();
+                        view.set_filter(filter);
+                    }
+                    KeyCode::Esc => view.set_filter(String::default()),
+                    _ => {}
+                }
+            }
+            if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+                self.searching = false;
+            }
+            return Action::None;
+        }
+        self.message.clear();
+        if key.code != KeyCode::Char('q') {
+            self.quit_confirm = false;
+        }
+        match key.code {
+            KeyCode::Char('q') => {
+                let unfinished = self.queue.unfinished();
+                if unfinished == 0 || self.quit_confirm {
+                    return Action::Quit;
+                }
+                self.quit_confirm = true;
+                self.message = format!("还有 {} 个下载没有完成, 再按一次q退出", unfinished);
+            }
+            KeyCode::Tab | KeyCode::Right => self.tab = (self.tab + 1) % TABS.len(),
+            KeyCode::BackTab | KeyCode::Left => self.tab = (self.tab + TABS.len() - 1) % TABS.len(),
+            KeyCode::Char('r') => return Action::Reload,
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Char('d') => self.enqueue(),
+            KeyCode::Esc | KeyCode::Backspace => {
+                let stack = &mut self.views[self.tab];
+                let filtered = stack.last().map(|view| !view.filter.is_empty());
+                match filtered {
+                    Some(true) => stack.last_mut().unwrap().set_filter(String::default()),
+                    _ if stack.len() > 1 => {
+                        stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+            KeyCode::Enter => {
+                let target = self.view().and_then(|view| {
+                    view.current()
+                        .map(|index| &view.entries[index])
+                        .map(|entry| (entry.target.clone(), entry.title.clone()))
+                });
+                match target {
+                    Some((target, title)) if target.can_open() => {
+                        return Action::Open(target, title)
+                    }
+                    Some(_) => self.enqueue(),
+                    None => {}
+                }
+            }
+            code => {
+                if let Some(view) = self.view() {
+                    match code {
+                        KeyCode::Up | KeyCode::Char('k') => view.move_cursor(-1),
+                        KeyCode::Down | KeyCode::Char('j') => view.move_cursor(1),
+                        KeyCode::PageUp => view.move_cursor(-10),
+                        KeyCode::PageDown => view.move_cursor(10),
+                        KeyCode::Home | KeyCode::Char('g') => view.move_cursor(i64::MIN / 2),
+                        KeyCode::End | KeyCode::Char('G') => view.move_cursor(i64::MAX / 2),
+                        KeyCode::Char(' ') => {
+                            if let Some(index) = view.current() {
+                                if view.entries[index].target.url().is_some()
+                                    && !view.selected.remove(&index)
+                                {
+                                    view.selected.insert(index);
+                                }
+                            }
+                            view.move_cursor(1);
+                        }
+                        KeyCode::Char('a') => {
+                            let visible = view
+                                .visible()
+                                .into_iter()
+                                .filter(|index| view.entries[*index].target.url().is_some())
+                                .collect::<Vec<usize>>();
+                            if visible.iter().all(|index| view.selected.contains(index)) {
+                                view.selected.clear();
+                            } else {
+                                view.selected.extend(visible);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Action::None
+    }
+}
+
+/// 全屏的终端界面, 浏览账号的收藏夹, 稍后再看, 关注的UP主和追番, 选择后加入下载队列
+pub(crate) async fn tui() -> crate::Result<()> {
+    let web_api = login_client().await?;
+    let nav = web_api.nav().await?;
+    if !nav.is_login {
+        return Err(anyhow::Error::msg(
+            "登录信息已失效, 请使用 bili-cli login 重新登录",
+        ));
+    }
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    let mut app = App {
+        tab: 0,
+        views: TABS.iter().map(|_| vec![]).collect(),
+        searching: false,
+        message: String::default(),
+        quit_confirm: false,
+        queue: DownloadQueue::new(),
+    };
+    let result = run(&mut terminal, &mut app, &web_api, nav.mid).await;
+    app.queue.stop();
+    result
+}
+
+async fn run(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    web_api: &WebApi,
+    mid: i64,
+) -> crate::Result<()> {
+    loop {
+        // 第一次打开分类时读取
+        if app.views[app.tab].is_empty() {
+            app.message = "加载中...".to_owned();
+            terminal.draw(|f| draw(f, app))?;
+            let view = match load_tab(app.tab, web_api, mid).await {
+                Ok(view) => {
+                    app.message = String::default();
+                    view
+                }
+                Err(err) => {
+                    app.message = format!("加载失败 : {}, 按r重试", err);
+                    View::new(TABS[app.tab].to_owned(), None, vec![])
+                }
+            };
+            app.views[app.tab].push(view);
+        }
+        terminal.draw(|f| draw(f, app))?;
+        // 没有按键时也要刷新下载队列
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match app.handle_key(key) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Open(target, title) => {
+                app.message = "加载中...".to_owned();
+                terminal.draw(|f| draw(f, app))?;
+                match open(web_api, &target, title).await {
+                    Ok(view) => {
+                        app.message = String::default();
+                        app.views[app.tab].push(view);
+                    }
+                    Err(err) => app.message = format!("加载失败 : {}", err),
+                }
+            }
+            Action::Reload => {
+                let source = app.views[app.tab].last().and_then(|view| {
+                    view.source
+                        .clone()
+                        .map(|source| (source, view.title.clone()))
+                });
+                match source {
+                    Some((source, title)) => {
+                        app.message = "加载中...".to_owned();
+                        terminal.draw(|f| draw(f, app))?;
+                        match open(web_api, &source, title).await {
+                            Ok(view) => {
+                                app.message = String::default();
+                                *app.views[app.tab].last_mut().unwrap() = view;
+                            }
+                            Err(err) => app.message = format!("加载失败 : {}", err),
+                        }
+                    }
+                    None => app.views[app.tab].clear(),
+                }
+            }
+        }
+    }
+}
+
+/// 分类的第一个列表
+async fn load_tab(tab: usize, web_api: &WebApi, mid: i64) -> crate::Result<View> {
+    let entries = match tab {
+        0 => web_api
+            .fav_folders(mid)
+            .await?
+            .into_iter()
+            .map(|folder| Entry {
+                title: folder.title,
+                detail: format!("{} 个视频", folder.media_count),
+                target: Target::FavFolder(mid, folder.id),
+            })
+            .collect(),
+        1 => web_api
+            .to_view()
+            .await?
+            .list
+            .into_iter()
+            .map(|video| Entry {
+                title: video.title,
+                detail: format!(
+                    "{} | {}:{:02}",
+                    video.owner.name,
+                    video.duration / 60,
+                    video.duration % 60
+                ),
+                target: Target::Video(video.bvid),
+            })
+            .collect(),
+        2 => {
+            let mut entries = vec![];
+            let mut pn = 1;
+            loop {
+                let page = web_api.followings(mid, pn, 50).await?;
+                let empty = page.list.is_empty();
+                entries.extend(page.list.into_iter().map(|user| Entry {
+                    title: user.uname,
+                    detail: user.sign,
+                    target: Target::Uploader(user.mid),
+                }));
+                if empty || entries.len() as i64 >= page.total {
+                    break;
+                }
+                pn += 1;
+            }
+            entries
+        }
+        _ => {
+            let mut entries = vec![];
+            let mut pn = 1;
+            loop {
+                let page = web_api.bangumi_follows(mid, pn, 30).await?;
+                let empty = page.list.is_empty();
+                entries.extend(page.list.into_iter().map(|season| Entry {
+                    title: season.title,
+                    detail: format!("{} | {}", season.season_type_name, season.new_ep.index_show),
+                    target: Target::Season(season.season_id),
+                }));
+                if empty || entries.len() as i64 >= page.total {
+                    break;
+                }
+                pn += 1;
+            }
+            entries
+        }
+    };
+    Ok(View::new(TABS[tab].to_owned(), None, entries))
+}
+
+/// 打开收藏夹或UP主, 列出其中的视频
+async fn open(web_api: &WebApi, target: &Target, title: String) -> crate::Result<View> {
+    let source = match target {
+        Target::FavFolder(_, fid) => sync::fetch_fav_list(web_api, *fid).await?,
+        Target::Uploader(mid) => sync::fetch_user_videos(*mid).await?,
+        _ => return Err(anyhow::Error::msg("只能打开收藏夹和UP主")),
+    };
+    let entries = source
+        .archives
+        .into_iter()
+        .map(|(bvid, title)| Entry {
+            title,
+            detail: bvid.clone(),
+            target: Target::Video(bvid),
+        })
+        .collect();
+    Ok(View::new(title, Some(target.clone()), entries))
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.size());
+    let tabs = Tabs::new(TABS.iter().map(|tab| Spans::from(*tab)).collect())
+        .block(Block::default().borders(Borders::ALL).title("bili-cli"))
+        .select(app.tab)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(tabs, rows[0]);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(rows[1]);
+    // 列表
+    let path = app.views[app.tab]
+        .iter()
+        .map(|view| view.title.as_str())
+        .collect::<Vec<&str>>()
+        .join(" > ");
+    if let Some(view) = app.views[app.tab].last_mut() {
+        let items = view
+            .visible()
+            .into_iter()
+            .map(|index| {
+                let entry = &view.entries[index];
+                let mark = if entry.target.url().is_none() {
+                    "    "
+                } else if view.selected.contains(&index) {
+                    "[x] "
+                } else {
+                    "[ ] "
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::raw(mark),
+                    Span::raw(entry.title.clone()),
+                    Span::styled(
+                        format!("  {}", entry.detail),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect::<Vec<ListItem>>();
+        let mut title = format!("{} ({})", path, view.entries.len());
+        if !view.filter.is_empty() {
+            title.push_str(format!(" 搜索 : {}", view.filter).as_str());
+        }
+        if !view.selected.is_empty() {
+            title.push_str(format!(" 已选择 {}", view.selected.len()).as_str());
+        }
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, columns[0], &mut view.state);
+    }
+    // 下载队列
+    let queue = app
+        .queue
+        .items
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            let (mark, status, color) = match &item.status {
+                QueueStatus::Waiting => ("…", "等待中".to_owned(), Color::DarkGray),
+                QueueStatus::Running(line) => ("↓", line.clone(), Color::Yellow),
+                QueueStatus::Done => ("✓", "完成".to_owned(), Color::Green),
+                QueueStatus::Failed(line) => ("✗", format!("失败 : {}", line), Color::Red),
+            };
+            ListItem::new(vec![
+                Spans::from(vec![
+                    Span::styled(format!("{} ", mark), Style::default().fg(color)),
+                    Span::raw(item.title.clone()),
+                ]),
+                Spans::from(Span::styled(
+                    format!("  {}", status),
+                    Style::default().fg(Color::DarkGray),
+                )),
+            ])
+        })
+        .collect::<Vec<ListItem>>();
+    let queue = List::new(queue).block(Block::default().borders(Borders::ALL).title("下载队列"));
+    f.render_widget(queue, columns[1]);
+    // 状态栏
+    let status = if app.searching {
+        let filter = app.views[app.tab]
+            .last()
+            .map(|view| view.filter.clone())
+            .unwrap_or_default();
+        format!("/{}  (回车确认, Esc取消)", filter)
+    } else if !app.message.is_empty() {
+        app.message.clone()
+    } else {
+        "Tab 切换  ↑↓ 移动  空格 选择  a 全选  回车 打开/下载  d 下载选中  / 搜索  r 刷新  Esc 返回  q 退出".to_owned()
+    };
+    f.render_widget(Paragraph::new(status), rows[2]);
+}
@@ -0,0 +1,312 @@
+use std::io::Write;
+use std::time::Duration;
+
+use chrono::{Local, TimeZone};
+use once_cell::sync::OnceCell;
+use tokio::time::sleep;
+
+use crate::api::WebApi;
+use crate::down::{
+    SERIES_PATTERN, USER_COLLECTION_DETAIL_PATTERN, USER_FAV_LIST_PATTERN, USER_SPACE_PATTERN,
+};
+use crate::entities::subscription;
+use crate::local::{
+    data_local_dir, delete_subscription, join_paths, load_subscriptions, save_subscription,
+};
+use crate::sync::{fetch_source, sync_source};
+use crate::{app, bangumi, checked_login_client, config};
+
+/// 两次检查之间等待的时间, 到了间隔的订阅才会被检查
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 日志文件
+static LOG_FILE: OnceCell<String> = OnceCell::new();
+
+/// 定时检查订阅
+pub(crate) async fn watch() -> crate::Result<()> {
+    match app::nested_subcommand() {
+        Some(subcommand) => match subcommand.as_str() {
+            "add" => add(app::subscription_url_value(), app::watch_interval_value()).await?,
+            "remove" => remove(app::subscription_url_value()).await?,
+            "list" => list().await?,
+            _ => app::print_help()?,
+        },
+        None => run(app::watch_once_value()).await?,
+    }
+    Ok(())
+}
+
+/// 订阅的类型
+fn subscription_kind(url: &str) -> Option<&'static str> {
+    // 合集和收藏夹的url中也有用户的id, 需要先匹配
+    if USER_COLLECTION_DETAIL_PATTERN.is_match(url) {
+        Some("collection")
+    } else if USER_FAV_LIST_PATTERN.is_match(url) {
+        Some("fav")
+    } else if USER_SPACE_PATTERN.is_match(url) {
+        Some("user")
+    } else if SERIES_PATTERN.is_match(url) {
+        Some("bangumi")
+    } else {
+        None
+    }
+}
+
+fn kind_name(kind: &str) -> &str {
+    match kind {
+        "collection" => "合集",
+        "fav" => "收藏夹",
+        "user" => "UP主",
+        "bangumi" => "番剧",
+        other => other,
+    }
+}
+
+/// 检查的间隔(秒)
+fn interval(item: &subscription::Model) -> i64 {
+    if item.interval > 0 {
+        item.interval
+    } else {
+        config::config_value("watch_interval")
+            .parse()
+            .unwrap_or(3600)
+    }
+}
+
+async fn add(url: String, interval: i64) -> crate::Result<()> {
+    let kind = subscription_kind(url.as_str())
+        .ok_or_else(|| anyhow::Error::msg(format!("只能订阅UP主, 收藏夹, 合集或番剧 : {}", url)))?;
+    save_subscription(subscription::Model {
+        url: url.clone(),
+        kind: kind.to_owned(),
+        interval,
+        last_checked: 0,
+    })
+    .await?;
+    println!("已订阅{} : {}", kind_name(kind), url);
+    Ok(())
+}
+
+async fn remove(url: String) -> crate::Result<()> {
+    if delete_subscription(url.as_str()).await? {
+        println!("已取消订阅 : {}", url);
+    } else {
+        println!("没有订阅 : {}", url);
+    }
+    Ok(())
+}
+
+async fn list() -> crate::Result<()> {
+    let subscriptions = load_subscriptions().await?;
+    if subscriptions.is_empty() {
+        println!("没有订阅, 请使用 bili-cli watch add <url> 添加");
+        return Ok(());
+    }
+    for item in subscriptions {
+        let last_checked = if item.last_checked == 0 {
+            "未检查".to_owned()
+        } else {
+            Local
+                .timestamp_opt(item.last_checked, 0)
+                .single()
+                .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        println!(
+            "[{}] {} (每 {} 秒检查, 上次检查 : {})",
+            kind_name(item.kind.as_str()),
+            item.url,
+            interval(&item),
+            last_checked
+        );
+    }
+    Ok(())
+}
+
+/// 记录日志, 同时输出到控制台和日志文件
+fn log(message: &str) {
+    let line = format!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+    println!("{}", line);
+    if let Some(path) = LOG_FILE.get() {
+        if let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+        {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+/// watch运行时持有的锁文件, 防止同时运行多个watch, 退出时删除
+struct WatchLock(String);
+
+impl WatchLock {
+    fn acquire() -> crate::Result<Self> {
+        let path = join_paths(vec![data_local_dir().as_str(), "watch.lock"]);
+        // 创建锁文件和检查锁文件是同一个操作, 同时启动的watch只有一个能创建成功
+        let mut file = match WatchLock::create(path.as_str()) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                let pid = std::fs::read_to_string(path.as_str()).unwrap_or_default();
+                if process_alive(pid.trim()) {
+                    return Err(anyhow::Error::msg(format!(
+                        "watch已经在运行 (pid {}), 如果没有在运行请删除 {}",
+                        pid.trim(),
+                        path
+                    )));
+                }
+                // 上次运行没有正常退出, 删除后重新创建
+                std::fs::remove_file(path.as_str())?;
+                WatchLock::create(path.as_str())?
+            }
+            Err(err) => return Err(err.into()),
+        };
+        file.write_all(std::process::id().to_string().as_bytes())?;
+        Ok(WatchLock(path))
+    }
+
+    fn create(path: &str) -> std::io::Result<std::fs::File> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+    }
+}
+
+impl Drop for WatchLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// 发送信号0检查进程是否存在, 没有权限时进程也存在
+#[cfg(unix)]
+fn process_alive(pid: &str) -> bool {
+    let pid = match pid.parse::<libc::pid_t>() {
+        Ok(pid) if pid > 0 => pid,
+        _ => return false,
+    };
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// 打开进程并检查是否已经退出
+#[cfg(windows)]
+fn process_alive(pid: &str) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    let pid = match pid.parse::<u32>() {
+        Ok(pid) if pid > 0 => pid,
+        _ => return false,
+    };
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle == 0 {
+            return false;
+        }
+        let mut code = 0;
+        let alive = GetExitCodeProcess(handle, &mut code) != 0 && code == STILL_ACTIVE as u32;
+        CloseHandle(handle);
+        alive
+    }
+}
+
+/// 不能检查进程是否存在时认为锁文件有效
+#[cfg(not(any(unix, windows)))]
+fn process_alive(pid: &str) -> bool {
+    !pid.is_empty()
+}
+
+/// 等待SIGTERM或者Ctrl+C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// 运行直到收到退出信号, once为true时只检查一次
+async fn run(once: bool) -> crate::Result<()> {
+    // 未登录时直接退出, 不需要锁文件
+    checked_login_client(false).await?;
+    let _lock = WatchLock::acquire()?;
+    let log_file = app::log_file_value()
+        .unwrap_or_else(|| join_paths(vec![data_local_dir().as_str(), "watch.log"]));
+    let _ = LOG_FILE.set(log_file.clone());
+    log(format!("开始运行, 日志文件 : {}", log_file).as_str());
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            result = poll_due() => result?,
+            _ = &mut shutdown => {
+                log("收到退出信号, 停止运行, 未完成的下载可以在下次使用 --resume 继续");
+                break;
+            }
+        }
+        if once {
+            break;
+        }
+        tokio::select! {
+            _ = sleep(POLL_INTERVAL) => {}
+            _ = &mut shutdown => {
+                log("收到退出信号, 停止运行");
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 检查到了间隔的订阅
+async fn poll_due() -> crate::Result<()> {
+    let due = load_subscriptions()
+        .await?
+        .into_iter()
+        .filter(|item| item.last_checked + interval(item) <= Local::now().timestamp())
+        .collect::<Vec<subscription::Model>>();
+    if due.is_empty() {
+        return Ok(());
+    }
+    // 长时间运行时登录信息可能过期, 每次检查前重新检查, 需要时刷新
+    let client = match checked_login_client(false).await {
+        Ok(client) => client,
+        Err(err) => {
+            log(format!("停止运行 : {}", err).as_str());
+            return Err(err);
+        }
+    };
+    for mut item in due {
+        log(format!("检查{} : {}", kind_name(item.kind.as_str()), item.url).as_str());
+        match poll(&client, &item).await {
+            Ok(_) => log(format!("完成 : {}", item.url).as_str()),
+            Err(err) => log(format!("失败 : {} : {}", item.url, err).as_str()),
+        }
+        item.last_checked = Local::now().timestamp();
+        save_subscription(item).await?;
+    }
+    Ok(())
+}
+
+/// 下载订阅中新的视频, 已经下载的会被跳过
+async fn poll(client: &WebApi, item: &subscription::Model) -> crate::Result<()> {
+    match item.kind.as_str() {
+        "bangumi" => bangumi::update_subscription(client, item.url.as_str()).await,
+        _ => {
+            let source = fetch_source(client, item.url.as_str()).await?;
+            sync_source(client, source, "keep").await
+        }
+    }
+}